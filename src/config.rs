@@ -0,0 +1,809 @@
+//! Layered configuration.
+//!
+//! Settings are resolved from the following sources, with later sources
+//! overriding earlier ones:
+//!
+//! 1. Built-in defaults
+//! 2. The global config file, `$XDG_CONFIG_HOME/git-commit-gpt/config.toml`
+//!    (falling back to `~/.config/git-commit-gpt/config.toml`)
+//! 3. The repository config file, `.git-commit-gpt.toml` in the work tree root
+//! 4. `commitgpt.*` git config keys, e.g. `commitgpt.model`, resolved
+//!    across the system, global, local and worktree scopes
+//! 5. `GIT_COMMIT_GPT_*` environment variables, e.g. `GIT_COMMIT_GPT_MODEL`
+//! 6. Command line flags
+
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+
+use crate::conventional;
+use crate::credentials::KeySources;
+use crate::exclude::DEFAULT_EXCLUDES;
+use crate::provider::{ProviderKind, Sampling};
+use crate::template;
+use crate::ticket::{self, Placement};
+
+pub const DEFAULT_PROMPT: &str =
+    "Given the following git diff, suggest a commit message that can be passed to `git commit`.";
+pub const DEFAULT_SYSTEM_MESSAGE: &str = "You are a helpful assistant.";
+pub const DEFAULT_COUNT: usize = 5;
+/// Recent commit messages aren't redacted, so sending them is opt-in.
+pub const DEFAULT_EXAMPLES: usize = 0;
+pub const DEFAULT_LANGUAGE: &str = "English";
+pub const DEFAULT_MAX_SUBJECT_LENGTH: usize = 72;
+
+const REPO_CONFIG_FILE: &str = ".git-commit-gpt.toml";
+const ENV_PREFIX: &str = "GIT_COMMIT_GPT_";
+const GIT_CONFIG_SECTION: &str = "commitgpt";
+
+#[derive(Debug)]
+pub enum Error {
+    Read(PathBuf, std::io::Error),
+    Parse(PathBuf, toml::de::Error),
+    /// An invalid value in an environment variable or git config key.
+    Invalid(String, String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Read(path, e) => write!(f, "failed to read {}: {}", path.display(), e),
+            Error::Parse(path, e) => write!(f, "failed to parse {}: {}", path.display(), e),
+            Error::Invalid(name, message) => {
+                write!(f, "invalid value for {}: {}", name, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub provider: Option<ProviderKind>,
+    pub model: Option<String>,
+    pub base_url: Option<String>,
+    pub api_version: Option<String>,
+    pub prompt: Option<String>,
+    /// The number of candidate messages to generate.
+    pub count: Option<usize>,
+    /// Whether to open the editor to amend the selected message.
+    pub amend: Option<bool>,
+    /// Shell commands that print the API key, per provider.
+    pub api_key_command: Option<PerProvider<String>>,
+    /// Files containing the API key, per provider.
+    pub api_key_file: Option<PerProvider<PathBuf>>,
+    /// The most tokens of diff to send, defaulting to what fits the model's
+    /// context window.
+    pub max_diff_tokens: Option<usize>,
+    /// Glob patterns of files to leave out of the prompt, replacing the
+    /// defaults.
+    pub exclude: Option<Vec<String>>,
+    /// Whether to redact secrets from the diff.
+    pub redact: Option<bool>,
+    /// Whether to abort instead of redacting when secrets are found.
+    pub redact_abort: Option<bool>,
+    /// Names of built-in redaction rules to disable, e.g. `high-entropy`.
+    pub redact_disable: Option<Vec<String>>,
+    /// Additional regular expressions to redact. Only read from config files
+    /// since patterns may contain commas.
+    pub redact_patterns: Option<Vec<String>>,
+    /// Whether to show the requests and ask before sending them.
+    pub confirm: Option<bool>,
+    /// Whether to generate Conventional Commits messages.
+    pub conventional: Option<bool>,
+    /// The allowed Conventional Commits types.
+    pub conventional_types: Option<Vec<String>>,
+    /// Maps path prefixes to Conventional Commits scopes, e.g.
+    /// `"crates/core" = "core"`. Only read from config files.
+    pub scopes: Option<BTreeMap<String, String>>,
+    /// Whether to generate a body along with the subject line.
+    pub body: Option<bool>,
+    /// The number of recent commit messages to include as examples of the
+    /// repository's style, or 0 to include none.
+    pub examples: Option<usize>,
+    /// Whether to only take examples from commits touching the staged paths.
+    pub examples_from_paths: Option<bool>,
+    /// A regular expression matching issue keys in the branch name.
+    pub ticket_pattern: Option<String>,
+    /// Where to insert issue keys into each message.
+    pub ticket_placement: Option<Placement>,
+    /// The text inserted into each message, with `{ticket}` replaced by the
+    /// issue keys.
+    pub ticket_template: Option<String>,
+    /// The name of, or path to, a prompt template replacing the prompt and
+    /// built-in instructions.
+    pub template: Option<String>,
+    /// The language to write commit messages in.
+    pub language: Option<String>,
+    /// The system message sent with each request.
+    pub system_message: Option<String>,
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    /// The most tokens to generate per completion.
+    pub max_tokens: Option<u32>,
+    /// Makes suggestions repeatable where the provider supports it.
+    pub seed: Option<u64>,
+    /// Whether to request structured output, defaulting to whether the
+    /// provider is known to support it.
+    pub structured_output: Option<bool>,
+    /// The longest subject line, in characters, of suggestions to keep.
+    pub max_subject_length: Option<usize>,
+}
+
+impl Config {
+    /// Loads every configuration layer and applies `cli` on top.
+    pub fn load(cli: Config) -> Result<Self, Error> {
+        let mut config = Config::default();
+        if let Some(path) = global_config_path() {
+            config.merge(Config::from_file(&path)?);
+        }
+        if let Some(path) = repo_config_path() {
+            let mut repo_config = Config::from_file(&path)?;
+            if let Some(name) = repo_config.global_only_setting() {
+                return Err(Error::Invalid(
+                    format!("{} in {}", name, path.display()),
+                    "only allowed in the global config file".to_string(),
+                ));
+            }
+            // A repository may only pick one of its own templates, rather
+            // than have any local file sent to the provider.
+            if let Some(name) = &repo_config.template {
+                let template = template::repo_template(name).ok_or_else(|| {
+                    Error::Invalid(
+                        format!("template in {}", path.display()),
+                        format!(
+                            "no template named '{}' in {}",
+                            name,
+                            template::REPO_TEMPLATE_DIR
+                        ),
+                    )
+                })?;
+                repo_config.template = Some(template.to_string_lossy().into_owned());
+            }
+            config.merge(repo_config);
+        }
+        config.merge(Config::from_git_config()?);
+        config.merge(Config::from_env()?);
+        config.merge(cli);
+        Ok(config)
+    }
+
+    /// The first setting that a freshly cloned repository mustn't control,
+    /// since it could run arbitrary commands, send the diff and API key to
+    /// another provider or server, or send secrets unredacted.
+    fn global_only_setting(&self) -> Option<&'static str> {
+        [
+            ("provider", self.provider.is_some()),
+            ("api_key_command", self.api_key_command.is_some()),
+            ("api_key_file", self.api_key_file.is_some()),
+            ("base_url", self.base_url.is_some()),
+            ("api_version", self.api_version.is_some()),
+            ("redact", self.redact.is_some()),
+            ("redact_disable", self.redact_disable.is_some()),
+            (
+                "template",
+                self.template
+                    .as_deref()
+                    .is_some_and(|name| !template::is_name(name)),
+            ),
+        ]
+        .into_iter()
+        .find(|&(_, set)| set)
+        .map(|(name, _)| name)
+    }
+
+    /// Overrides any settings in `self` that are also set in `other`.
+    fn merge(&mut self, other: Config) {
+        let Config {
+            provider,
+            model,
+            base_url,
+            api_version,
+            prompt,
+            count,
+            amend,
+            api_key_command,
+            api_key_file,
+            max_diff_tokens,
+            exclude,
+            redact,
+            redact_abort,
+            redact_disable,
+            redact_patterns,
+            confirm,
+            conventional,
+            conventional_types,
+            scopes,
+            body,
+            examples,
+            examples_from_paths,
+            ticket_pattern,
+            ticket_placement,
+            ticket_template,
+            template,
+            language,
+            system_message,
+            temperature,
+            top_p,
+            max_tokens,
+            seed,
+            structured_output,
+            max_subject_length,
+        } = other;
+        self.provider = provider.or(self.provider);
+        self.model = model.or(self.model.take());
+        self.base_url = base_url.or(self.base_url.take());
+        self.api_version = api_version.or(self.api_version.take());
+        self.prompt = prompt.or(self.prompt.take());
+        self.count = count.or(self.count);
+        self.amend = amend.or(self.amend);
+        self.api_key_command = merge_per_provider(self.api_key_command.take(), api_key_command);
+        self.api_key_file = merge_per_provider(self.api_key_file.take(), api_key_file);
+        self.max_diff_tokens = max_diff_tokens.or(self.max_diff_tokens);
+        self.exclude = exclude.or(self.exclude.take());
+        self.redact = redact.or(self.redact);
+        self.redact_abort = redact_abort.or(self.redact_abort);
+        self.redact_disable = redact_disable.or(self.redact_disable.take());
+        self.redact_patterns = redact_patterns.or(self.redact_patterns.take());
+        self.confirm = confirm.or(self.confirm);
+        self.conventional = conventional.or(self.conventional);
+        self.conventional_types = conventional_types.or(self.conventional_types.take());
+        self.scopes = scopes.or(self.scopes.take());
+        self.body = body.or(self.body);
+        self.examples = examples.or(self.examples);
+        self.examples_from_paths = examples_from_paths.or(self.examples_from_paths);
+        self.ticket_pattern = ticket_pattern.or(self.ticket_pattern.take());
+        self.ticket_placement = ticket_placement.or(self.ticket_placement);
+        self.ticket_template = ticket_template.or(self.ticket_template.take());
+        self.template = template.or(self.template.take());
+        self.language = language.or(self.language.take());
+        self.system_message = system_message.or(self.system_message.take());
+        self.temperature = temperature.or(self.temperature);
+        self.top_p = top_p.or(self.top_p);
+        self.max_tokens = max_tokens.or(self.max_tokens);
+        self.seed = seed.or(self.seed);
+        self.structured_output = structured_output.or(self.structured_output);
+        self.max_subject_length = max_subject_length.or(self.max_subject_length);
+    }
+
+    /// Reads a TOML config file, treating a missing file as empty.
+    fn from_file(path: &Path) -> Result<Self, Error> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(Error::Read(path.to_path_buf(), e)),
+        };
+        toml::from_str(&contents).map_err(|e| Error::Parse(path.to_path_buf(), e))
+    }
+
+    fn from_env() -> Result<Self, Error> {
+        Ok(Config {
+            provider: env("PROVIDER", |s| ProviderKind::from_str(s, true))?,
+            model: env("MODEL", |s| Ok(s.to_string()))?,
+            base_url: env("BASE_URL", |s| Ok(s.to_string()))?,
+            api_version: env("API_VERSION", |s| Ok(s.to_string()))?,
+            prompt: env("PROMPT", |s| Ok(s.to_string()))?,
+            count: env("COUNT", |s| usize::from_str(s).map_err(|e| e.to_string()))?,
+            amend: env("AMEND", |s| bool::from_str(s).map_err(|e| e.to_string()))?,
+            api_key_command: per_provider(|name| {
+                env(&format!("{}_API_KEY_COMMAND", name.to_uppercase()), |s| {
+                    Ok(s.to_string())
+                })
+            })?,
+            api_key_file: per_provider(|name| {
+                env(&format!("{}_API_KEY_FILE", name.to_uppercase()), |s| {
+                    Ok(PathBuf::from(s))
+                })
+            })?,
+            max_diff_tokens: env("MAX_DIFF_TOKENS", |s| {
+                usize::from_str(s).map_err(|e| e.to_string())
+            })?,
+            exclude: env("EXCLUDE", |s| Ok(split_list(s)))?,
+            redact: env("REDACT", |s| bool::from_str(s).map_err(|e| e.to_string()))?,
+            redact_abort: env("REDACT_ABORT", |s| {
+                bool::from_str(s).map_err(|e| e.to_string())
+            })?,
+            redact_disable: env("REDACT_DISABLE", |s| Ok(split_list(s)))?,
+            redact_patterns: None,
+            confirm: env("CONFIRM", |s| bool::from_str(s).map_err(|e| e.to_string()))?,
+            conventional: env("CONVENTIONAL", |s| {
+                bool::from_str(s).map_err(|e| e.to_string())
+            })?,
+            conventional_types: env("CONVENTIONAL_TYPES", |s| Ok(split_list(s)))?,
+            scopes: None,
+            body: env("BODY", |s| bool::from_str(s).map_err(|e| e.to_string()))?,
+            examples: env("EXAMPLES", |s| {
+                usize::from_str(s).map_err(|e| e.to_string())
+            })?,
+            examples_from_paths: env("EXAMPLES_FROM_PATHS", |s| {
+                bool::from_str(s).map_err(|e| e.to_string())
+            })?,
+            ticket_pattern: env("TICKET_PATTERN", |s| Ok(s.to_string()))?,
+            ticket_placement: env("TICKET_PLACEMENT", |s| Placement::from_str(s, true))?,
+            ticket_template: env("TICKET_TEMPLATE", |s| Ok(s.to_string()))?,
+            template: env("TEMPLATE", |s| Ok(s.to_string()))?,
+            language: env("LANGUAGE", |s| Ok(s.to_string()))?,
+            system_message: env("SYSTEM_MESSAGE", |s| Ok(s.to_string()))?,
+            temperature: env("TEMPERATURE", |s| {
+                f64::from_str(s).map_err(|e| e.to_string())
+            })?,
+            top_p: env("TOP_P", |s| f64::from_str(s).map_err(|e| e.to_string()))?,
+            max_tokens: env("MAX_TOKENS", |s| {
+                u32::from_str(s).map_err(|e| e.to_string())
+            })?,
+            seed: env("SEED", |s| u64::from_str(s).map_err(|e| e.to_string()))?,
+            structured_output: env("STRUCTURED_OUTPUT", |s| {
+                bool::from_str(s).map_err(|e| e.to_string())
+            })?,
+            max_subject_length: env("MAX_SUBJECT_LENGTH", |s| {
+                usize::from_str(s).map_err(|e| e.to_string())
+            })?,
+        })
+    }
+
+    fn from_git_config() -> Result<Self, Error> {
+        let string = |s: &str| Ok(s.to_string());
+        let boolean = |s: &str| Ok(s == "true");
+        let git_config = GitConfig::read()?;
+        let no_amend = git_config.get("noAmend", "bool", boolean)?;
+        Ok(Config {
+            provider: git_config.get("provider", "", |s| ProviderKind::from_str(s, true))?,
+            model: git_config.get("model", "", string)?,
+            base_url: git_config.get("baseUrl", "", string)?,
+            api_version: git_config.get("apiVersion", "", string)?,
+            prompt: git_config.get("prompt", "", string)?,
+            count: git_config.get("count", "int", |s| {
+                usize::from_str(s).map_err(|e| e.to_string())
+            })?,
+            amend: match no_amend {
+                Some(no_amend) => Some(!no_amend),
+                None => git_config.get("amend", "bool", boolean)?,
+            },
+            api_key_command: per_provider(|name| {
+                git_config.get(&format!("{}.apiKeyCommand", name), "", string)
+            })?,
+            api_key_file: per_provider(|name| {
+                git_config.get(&format!("{}.apiKeyFile", name), "path", |s| {
+                    Ok(PathBuf::from(s))
+                })
+            })?,
+            max_diff_tokens: git_config.get("maxDiffTokens", "int", |s| {
+                usize::from_str(s).map_err(|e| e.to_string())
+            })?,
+            exclude: git_config.get("exclude", "", |s| Ok(split_list(s)))?,
+            redact: git_config.get("redact", "bool", boolean)?,
+            redact_abort: git_config.get("redactAbort", "bool", boolean)?,
+            redact_disable: git_config.get("redactDisable", "", |s| Ok(split_list(s)))?,
+            redact_patterns: None,
+            confirm: git_config.get("confirm", "bool", boolean)?,
+            conventional: git_config.get("conventional", "bool", boolean)?,
+            conventional_types: git_config.get("conventionalTypes", "", |s| Ok(split_list(s)))?,
+            scopes: None,
+            body: git_config.get("body", "bool", boolean)?,
+            examples: git_config.get("examples", "int", |s| {
+                usize::from_str(s).map_err(|e| e.to_string())
+            })?,
+            examples_from_paths: git_config.get("examplesFromPaths", "bool", boolean)?,
+            ticket_pattern: git_config.get("ticketPattern", "", string)?,
+            ticket_placement: git_config
+                .get("ticketPlacement", "", |s| Placement::from_str(s, true))?,
+            ticket_template: git_config.get("ticketTemplate", "", string)?,
+            template: git_config.get("template", "", string)?,
+            language: git_config.get("language", "", string)?,
+            system_message: git_config.get("systemMessage", "", string)?,
+            temperature: git_config.get("temperature", "", |s| {
+                f64::from_str(s).map_err(|e| e.to_string())
+            })?,
+            top_p: git_config.get("topP", "", |s| f64::from_str(s).map_err(|e| e.to_string()))?,
+            max_tokens: git_config.get("maxTokens", "int", |s| {
+                u32::from_str(s).map_err(|e| e.to_string())
+            })?,
+            seed: git_config.get("seed", "int", |s| {
+                u64::from_str(s).map_err(|e| e.to_string())
+            })?,
+            structured_output: git_config.get("structuredOutput", "bool", boolean)?,
+            max_subject_length: git_config.get("maxSubjectLength", "int", |s| {
+                usize::from_str(s).map_err(|e| e.to_string())
+            })?,
+        })
+    }
+
+    pub fn provider(&self) -> ProviderKind {
+        self.provider.unwrap_or(ProviderKind::OpenAI)
+    }
+
+    pub fn prompt(&self) -> &str {
+        self.prompt.as_deref().unwrap_or(DEFAULT_PROMPT)
+    }
+
+    pub fn count(&self) -> usize {
+        // Asking for no suggestions at all is taken to mean one.
+        self.count.unwrap_or(DEFAULT_COUNT).max(1)
+    }
+
+    pub fn amend(&self) -> bool {
+        self.amend.unwrap_or(true)
+    }
+
+    pub fn exclude(&self) -> Vec<String> {
+        match &self.exclude {
+            Some(exclude) => exclude.clone(),
+            None => DEFAULT_EXCLUDES.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    pub fn redact(&self) -> bool {
+        self.redact.unwrap_or(true)
+    }
+
+    pub fn redact_abort(&self) -> bool {
+        self.redact_abort.unwrap_or(false)
+    }
+
+    pub fn confirm(&self) -> bool {
+        self.confirm.unwrap_or(false)
+    }
+
+    pub fn conventional(&self) -> bool {
+        self.conventional.unwrap_or(false)
+    }
+
+    pub fn conventional_types(&self) -> Vec<String> {
+        match &self.conventional_types {
+            Some(types) => types.clone(),
+            None => conventional::DEFAULT_TYPES
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        }
+    }
+
+    pub fn body(&self) -> bool {
+        self.body.unwrap_or(false)
+    }
+
+    pub fn examples(&self) -> usize {
+        self.examples.unwrap_or(DEFAULT_EXAMPLES)
+    }
+
+    pub fn examples_from_paths(&self) -> bool {
+        self.examples_from_paths.unwrap_or(false)
+    }
+
+    pub fn ticket_pattern(&self) -> &str {
+        self.ticket_pattern
+            .as_deref()
+            .unwrap_or(ticket::DEFAULT_PATTERN)
+    }
+
+    pub fn ticket_placement(&self) -> Placement {
+        self.ticket_placement.unwrap_or(Placement::None)
+    }
+
+    pub fn language(&self) -> &str {
+        self.language.as_deref().unwrap_or(DEFAULT_LANGUAGE)
+    }
+
+    pub fn system_message(&self) -> &str {
+        self.system_message
+            .as_deref()
+            .unwrap_or(DEFAULT_SYSTEM_MESSAGE)
+    }
+
+    pub fn max_subject_length(&self) -> usize {
+        self.max_subject_length
+            .unwrap_or(DEFAULT_MAX_SUBJECT_LENGTH)
+    }
+
+    pub fn sampling(&self) -> Sampling {
+        Sampling {
+            temperature: self.temperature,
+            top_p: self.top_p,
+            max_tokens: self.max_tokens,
+            seed: self.seed,
+        }
+    }
+
+    pub fn key_sources(&self) -> KeySources {
+        let provider = self.provider();
+        KeySources {
+            command: self
+                .api_key_command
+                .as_ref()
+                .and_then(|commands| commands.get(&provider))
+                .cloned(),
+            file: self
+                .api_key_file
+                .as_ref()
+                .and_then(|files| files.get(&provider))
+                .cloned(),
+        }
+    }
+}
+
+/// Splits a comma separated list, as used by list settings in the
+/// environment and git config.
+fn split_list(s: &str) -> Vec<String> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Reads `GIT_COMMIT_GPT_<name>`, parsing it with `parse` when set.
+/// Settings kept per provider, e.g. an `[api_key_command]` table, so that a
+/// key meant for one provider is never sent to another.
+pub type PerProvider<T> = BTreeMap<ProviderKind, T>;
+
+/// `value` for every provider, for settings given for a single run.
+pub fn for_every_provider<T: Clone>(value: T) -> PerProvider<T> {
+    ProviderKind::value_variants()
+        .iter()
+        .map(|&kind| (kind, value.clone()))
+        .collect()
+}
+
+/// Reads a per-provider setting with `read`, given each provider's name.
+fn per_provider<T>(
+    mut read: impl FnMut(&str) -> Result<Option<T>, Error>,
+) -> Result<Option<PerProvider<T>>, Error> {
+    let mut values = PerProvider::new();
+    for &kind in ProviderKind::value_variants() {
+        if let Some(value) = read(kind.name())? {
+            values.insert(kind, value);
+        }
+    }
+    Ok((!values.is_empty()).then_some(values))
+}
+
+/// Overrides per-provider settings one provider at a time.
+fn merge_per_provider<T>(
+    base: Option<PerProvider<T>>,
+    other: Option<PerProvider<T>>,
+) -> Option<PerProvider<T>> {
+    match (base, other) {
+        (Some(mut base), Some(other)) => {
+            base.extend(other);
+            Some(base)
+        }
+        (base, other) => other.or(base),
+    }
+}
+
+fn env<T>(name: &str, parse: impl Fn(&str) -> Result<T, String>) -> Result<Option<T>, Error> {
+    let name = format!("{}{}", ENV_PREFIX, name);
+    match std::env::var(&name) {
+        Ok(value) => parse(&value).map(Some).map_err(|e| Error::Invalid(name, e)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// The `commitgpt.*` git config, read with a single `git config` call.
+#[derive(Default)]
+struct GitConfig {
+    /// Values by lowercased key, `None` for keys given without a value.
+    values: HashMap<String, Option<String>>,
+}
+
+impl GitConfig {
+    fn read() -> Result<Self, Error> {
+        let output = match Command::new("git")
+            .args(["config", "--null", "--get-regexp"])
+            .arg(format!(r"^{}\.", GIT_CONFIG_SECTION))
+            .output()
+        {
+            Ok(output) => output,
+            // Without git there is no git config to read.
+            Err(_) => return Ok(Self::default()),
+        };
+
+        match output.status.code() {
+            Some(0) => {}
+            // No keys are set.
+            Some(1) => return Ok(Self::default()),
+            _ => {
+                return Err(Error::Invalid(
+                    GIT_CONFIG_SECTION.to_string(),
+                    String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                ))
+            }
+        }
+
+        Ok(Self::parse(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// Parses `git config --null --get-regexp` output, where each entry is
+    /// the key, then a newline and the value unless it has none. Later
+    /// entries win, as with `git config --get`.
+    fn parse(output: &str) -> Self {
+        let values = output
+            .split('\0')
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| match entry.split_once('\n') {
+                Some((key, value)) => (key.to_string(), Some(value.to_string())),
+                None => (entry.to_string(), None),
+            })
+            .collect();
+        Self { values }
+    }
+
+    /// Reads `commitgpt.<key>`, canonicalising the value as `ty` (`bool`,
+    /// `int` or `path`) when non-empty and parsing it with `parse`.
+    fn get<T>(
+        &self,
+        key: &str,
+        ty: &str,
+        parse: impl Fn(&str) -> Result<T, String>,
+    ) -> Result<Option<T>, Error> {
+        let key = format!("{}.{}", GIT_CONFIG_SECTION, key);
+        let value = match self.values.get(&key.to_lowercase()) {
+            Some(value) => value.as_deref(),
+            None => return Ok(None),
+        };
+        let value = match ty {
+            "bool" => git_bool(value).map(|b| b.to_string()),
+            "int" => git_int(value.unwrap_or_default()).map(|n| n.to_string()),
+            "path" => Ok(git_path(value.unwrap_or_default())),
+            _ => Ok(value.unwrap_or_default().to_string()),
+        };
+        value
+            .and_then(|value| parse(&value))
+            .map(Some)
+            .map_err(|e| Error::Invalid(key, e))
+    }
+}
+
+/// Interprets a value the way `git config --type=bool` does, where a key
+/// without a value is true.
+fn git_bool(value: Option<&str>) -> Result<bool, String> {
+    let value = match value {
+        Some(value) => value.to_lowercase(),
+        None => return Ok(true),
+    };
+    match value.as_str() {
+        "true" | "yes" | "on" => Ok(true),
+        "false" | "no" | "off" | "" => Ok(false),
+        _ => git_int(&value)
+            .map(|n| n != 0)
+            .map_err(|_| format!("bad boolean config value '{}'", value)),
+    }
+}
+
+/// Interprets a value the way `git config --type=int` does, including the
+/// `k`, `m` and `g` suffixes.
+fn git_int(value: &str) -> Result<i64, String> {
+    let invalid = || format!("bad numeric config value '{}'", value);
+    let (digits, factor) = match value.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&value[..i], 1 << 10),
+        Some((i, 'm' | 'M')) => (&value[..i], 1 << 20),
+        Some((i, 'g' | 'G')) => (&value[..i], 1 << 30),
+        _ => (value, 1),
+    };
+    i64::from_str(digits)
+        .ok()
+        .and_then(|n| n.checked_mul(factor))
+        .ok_or_else(invalid)
+}
+
+/// Expands a leading `~/` the way `git config --type=path` does.
+fn git_path(value: &str) -> String {
+    match (value.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => format!("{}/{}", home.trim_end_matches('/'), rest),
+        _ => value.to_string(),
+    }
+}
+
+fn global_config_path() -> Option<PathBuf> {
+    Some(global_config_dir()?.join("config.toml"))
+}
+
+/// The directory holding the global config file.
+pub fn global_config_dir() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("git-commit-gpt"))
+}
+
+fn repo_config_path() -> Option<PathBuf> {
+    Some(repo_root()?.join(REPO_CONFIG_FILE))
+}
+
+/// The root of the current work tree.
+pub fn repo_root() -> Option<PathBuf> {
+    Some(PathBuf::from(git(&["rev-parse", "--show-toplevel"])?))
+}
+
+/// The current branch, or `None` when `HEAD` is detached.
+pub fn current_branch() -> Option<String> {
+    // `rev-parse` fails on a branch without commits yet, which
+    // `symbolic-ref` still resolves.
+    let branch = git(&["rev-parse", "--abbrev-ref", "HEAD"])
+        .or_else(|| git(&["symbolic-ref", "--short", "-q", "HEAD"]))?;
+    (branch != "HEAD").then_some(branch)
+}
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8(output.stdout).ok()?.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn git_config_parses_entries() {
+        let git_config = GitConfig::parse(
+            "commitgpt.model\ngpt-4o\0commitgpt.body\0commitgpt.prompt\nline one\nline two\0commitgpt.model\ngpt-4o-mini\0",
+        );
+        let string = |s: &str| Ok(s.to_string());
+        assert_eq!(
+            git_config.get("model", "", string).unwrap().as_deref(),
+            Some("gpt-4o-mini")
+        );
+        assert_eq!(
+            git_config.get("prompt", "", string).unwrap().as_deref(),
+            Some("line one\nline two")
+        );
+        assert_eq!(
+            git_config.get("body", "bool", |s| Ok(s == "true")).unwrap(),
+            Some(true)
+        );
+        assert_eq!(git_config.get("language", "", string).unwrap(), None);
+    }
+
+    #[test]
+    fn git_config_matches_keys_case_insensitively() {
+        let git_config = GitConfig::parse("commitgpt.maxtokens\n2k\0");
+        assert_eq!(
+            git_config
+                .get("maxTokens", "int", |s| u32::from_str(s)
+                    .map_err(|e| e.to_string()))
+                .unwrap(),
+            Some(2048)
+        );
+    }
+
+    #[test]
+    fn git_bool_accepts_what_git_does() {
+        for value in [
+            None,
+            Some("true"),
+            Some("Yes"),
+            Some("on"),
+            Some("1"),
+            Some("-2"),
+        ] {
+            assert_eq!(git_bool(value), Ok(true), "{:?}", value);
+        }
+        for value in ["false", "NO", "off", "0", ""] {
+            assert_eq!(git_bool(Some(value)), Ok(false), "{:?}", value);
+        }
+        assert!(git_bool(Some("maybe")).is_err());
+    }
+
+    #[test]
+    fn git_int_applies_suffixes() {
+        assert_eq!(git_int("42"), Ok(42));
+        assert_eq!(git_int("-3"), Ok(-3));
+        assert_eq!(git_int("2k"), Ok(2048));
+        assert_eq!(git_int("1M"), Ok(1 << 20));
+        assert_eq!(git_int("3g"), Ok(3 << 30));
+        assert!(git_int("").is_err());
+        assert!(git_int("k").is_err());
+        assert!(git_int("1.5").is_err());
+        assert!(git_int("9223372036854775807k").is_err());
+    }
+}
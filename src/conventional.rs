@@ -0,0 +1,163 @@
+//! Conventional Commits (`type(scope): subject`) support.
+
+use regex::Regex;
+
+pub const DEFAULT_TYPES: &[&str] = &[
+    "feat", "fix", "docs", "style", "refactor", "perf", "test", "build", "ci", "chore", "revert",
+];
+
+/// Common variations models use instead of the standard types.
+const TYPE_ALIASES: &[(&str, &str)] = &[
+    ("feature", "feat"),
+    ("bugfix", "fix"),
+    ("bug", "fix"),
+    ("hotfix", "fix"),
+    ("doc", "docs"),
+    ("tests", "test"),
+    ("refactoring", "refactor"),
+    ("performance", "perf"),
+];
+
+pub struct Conventional {
+    types: Vec<String>,
+    /// Scopes inferred from the staged paths.
+    scopes: Vec<String>,
+    header: Regex,
+}
+
+impl Conventional {
+    pub fn new(types: Vec<String>, scopes: Vec<String>) -> Self {
+        Self {
+            types,
+            scopes,
+            header: Regex::new(
+                r"^(?P<type>[A-Za-z]+)\s*(?:\(\s*(?P<scope>[^()]+?)\s*\))?\s*(?P<breaking>!)?\s*:\s*(?P<subject>\S.*)$",
+            )
+            .unwrap(),
+        }
+    }
+
+    /// Instructions appended to the prompt.
+    pub fn instructions(&self) -> String {
+        let mut instructions = format!(
+            "Use the Conventional Commits format `type(scope): subject`, where type is one of {} and the scope is optional.",
+            self.types.join(", ")
+        );
+        match self.scopes.as_slice() {
+            [] => {}
+            [scope] => instructions.push_str(&format!(" Use `{}` as the scope.", scope)),
+            scopes => instructions.push_str(&format!(
+                " The changes touch the scopes {}, use the most relevant one as the scope.",
+                scopes.join(", ")
+            )),
+        }
+        instructions
+    }
+
+    /// Normalises `message` into `type(scope): subject`, or returns `None` if
+    /// it can't be parsed or uses an unknown type.
+    pub fn repair(&self, message: &str) -> Option<String> {
+        let caps = self.header.captures(message.trim())?;
+
+        let ty = caps["type"].to_lowercase();
+        let ty = TYPE_ALIASES
+            .iter()
+            .find(|(alias, _)| *alias == ty)
+            .map_or(ty.as_str(), |(_, ty)| ty)
+            .to_string();
+        if !self.types.contains(&ty) {
+            return None;
+        }
+
+        let scope = match (caps.name("scope"), self.scopes.as_slice()) {
+            (Some(scope), _) => format!("({})", scope.as_str()),
+            // Fill in the scope when there's no doubt about what it is.
+            (None, [scope]) => format!("({})", scope),
+            (None, _) => String::new(),
+        };
+        let breaking = if caps.name("breaking").is_some() {
+            "!"
+        } else {
+            ""
+        };
+        Some(format!(
+            "{}{}{}: {}",
+            ty,
+            scope,
+            breaking,
+            caps["subject"].trim()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conventional(scopes: &[&str]) -> Conventional {
+        Conventional::new(
+            DEFAULT_TYPES.iter().map(|ty| ty.to_string()).collect(),
+            scopes.iter().map(|scope| scope.to_string()).collect(),
+        )
+    }
+
+    #[test]
+    fn repair_keeps_valid_headers() {
+        let conventional = conventional(&[]);
+        assert_eq!(
+            conventional.repair("feat(parser): add arrays").as_deref(),
+            Some("feat(parser): add arrays")
+        );
+        assert_eq!(
+            conventional.repair("fix!: drop support for v1").as_deref(),
+            Some("fix!: drop support for v1")
+        );
+    }
+
+    #[test]
+    fn repair_normalises_spacing_case_and_aliases() {
+        let conventional = conventional(&[]);
+        assert_eq!(
+            conventional
+                .repair("  Feature ( api ) :  add endpoint ")
+                .as_deref(),
+            Some("feat(api): add endpoint")
+        );
+        assert_eq!(
+            conventional
+                .repair("BugFix !: handle empty input")
+                .as_deref(),
+            Some("fix!: handle empty input")
+        );
+    }
+
+    #[test]
+    fn repair_fills_in_an_unambiguous_scope() {
+        assert_eq!(
+            conventional(&["cli"])
+                .repair("docs: update usage")
+                .as_deref(),
+            Some("docs(cli): update usage")
+        );
+        assert_eq!(
+            conventional(&["cli", "core"])
+                .repair("docs: update usage")
+                .as_deref(),
+            Some("docs: update usage")
+        );
+        assert_eq!(
+            conventional(&["cli"])
+                .repair("docs(readme): update usage")
+                .as_deref(),
+            Some("docs(readme): update usage")
+        );
+    }
+
+    #[test]
+    fn repair_rejects_unknown_types_and_plain_subjects() {
+        let conventional = conventional(&[]);
+        assert_eq!(conventional.repair("update: bump version"), None);
+        assert_eq!(conventional.repair("Add a parser"), None);
+        assert_eq!(conventional.repair("feat:"), None);
+    }
+}
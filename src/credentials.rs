@@ -0,0 +1,99 @@
+//! API key lookup.
+//!
+//! Keys are looked up from, in order: the provider's environment variable,
+//! its `api_key_command`, its `api_key_file`, and finally the freedesktop
+//! Secret Service keyring via `secret-tool`, using the attributes
+//! `service git-commit-gpt account <provider>`.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::provider::Error;
+
+const KEYRING_SERVICE: &str = "git-commit-gpt";
+
+/// Alternative places to read an API key from.
+#[derive(Clone, Debug, Default)]
+pub struct KeySources {
+    /// A shell command that prints the key, e.g. `pass show openai`.
+    pub command: Option<String>,
+    /// A file containing the key.
+    pub file: Option<PathBuf>,
+}
+
+impl KeySources {
+    /// Looks up the key for `account`, returning `None` if no source has one.
+    pub fn lookup(&self, env_var: &str, account: &str) -> Result<Option<String>, Error> {
+        // An empty variable is as good as unset.
+        if let Ok(key) = std::env::var(env_var) {
+            if !key.trim().is_empty() {
+                return Ok(Some(key.trim().to_string()));
+            }
+        }
+        if let Some(command) = &self.command {
+            return run_command(command).map(Some);
+        }
+        if let Some(file) = &self.file {
+            let key = std::fs::read_to_string(file).map_err(|e| {
+                Error::Provider(format!("failed to read {}: {}", file.display(), e))
+            })?;
+            if key.trim().is_empty() {
+                return Err(Error::Provider(format!(
+                    "{} contains no API key",
+                    file.display()
+                )));
+            }
+            return Ok(Some(key.trim().to_string()));
+        }
+        Ok(keyring_lookup(account))
+    }
+
+    /// Like `lookup`, but fails with an explanation when no key is found.
+    pub fn require(&self, env_var: &str, account: &str) -> Result<String, Error> {
+        self.lookup(env_var, account)?.ok_or_else(|| {
+            Error::Provider(format!(
+                "no API key found for {}. Set {}, configure api_key_command or \
+                 api_key_file, or store one in your keyring with \
+                 `secret-tool store --label={} service {} account {}`",
+                account, env_var, KEYRING_SERVICE, KEYRING_SERVICE, account
+            ))
+        })
+    }
+}
+
+fn run_command(command: &str) -> Result<String, Error> {
+    let output = Command::new("sh")
+        .args(["-c", command])
+        .output()
+        .map_err(|e| Error::Provider(format!("failed to run `{}`: {}", command, e)))?;
+    if !output.status.success() {
+        return Err(Error::Provider(format!(
+            "`{}` failed with {} {}",
+            command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    // Tools like `pass` print the secret on the first line.
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let key = stdout.lines().next().unwrap_or_default().trim();
+    if key.is_empty() {
+        return Err(Error::Provider(format!("`{}` printed no API key", command)));
+    }
+    Ok(key.to_string())
+}
+
+/// Reads the key from the Secret Service keyring, if `secret-tool` is
+/// installed and has one stored.
+fn keyring_lookup(account: &str) -> Option<String> {
+    let output = Command::new("secret-tool")
+        .args(["lookup", "service", KEYRING_SERVICE, "account", account])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let key = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    (!key.is_empty()).then_some(key)
+}
@@ -0,0 +1,275 @@
+//! Parsing and trimming of `git diff` output.
+
+/// A rough token count, assuming about four bytes per token.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(4)
+}
+
+/// The diff for a single file.
+pub struct FileDiff<'a> {
+    pub path: String,
+    /// The `diff --git`, index and `---`/`+++` lines.
+    header: Vec<&'a str>,
+    hunks: Vec<Hunk<'a>>,
+    pub additions: usize,
+    pub deletions: usize,
+}
+
+struct Hunk<'a> {
+    /// The `@@ -a,b +c,d @@` line.
+    header: &'a str,
+    lines: Vec<&'a str>,
+}
+
+impl<'a> FileDiff<'a> {
+    /// Renders the diff, keeping at most `max_lines` lines of each hunk.
+    fn render(&self, max_lines: usize, out: &mut String) {
+        for line in &self.header {
+            out.push_str(line);
+            out.push('\n');
+        }
+        for hunk in &self.hunks {
+            out.push_str(hunk.header);
+            out.push('\n');
+            for line in hunk.lines.iter().take(max_lines) {
+                out.push_str(line);
+                out.push('\n');
+            }
+            if hunk.lines.len() > max_lines {
+                out.push_str(&format!(
+                    "[... {} more lines]\n",
+                    hunk.lines.len() - max_lines
+                ));
+            }
+        }
+    }
+
+    fn longest_hunk(&self) -> usize {
+        self.hunks.iter().map(|h| h.lines.len()).max().unwrap_or(0)
+    }
+
+    /// A `git diff --stat` style summary line.
+    pub fn stat(&self) -> String {
+        format!("{} | +{} -{}", self.path, self.additions, self.deletions)
+    }
+
+    /// Whether the file was added, deleted or updated.
+    pub fn change(&self) -> &'static str {
+        if self
+            .header
+            .iter()
+            .any(|line| line.starts_with("new file mode"))
+        {
+            "added"
+        } else if self
+            .header
+            .iter()
+            .any(|line| line.starts_with("deleted file mode"))
+        {
+            "deleted"
+        } else {
+            "updated"
+        }
+    }
+
+    pub fn text(&self) -> String {
+        let mut out = String::new();
+        self.render(usize::MAX, &mut out);
+        out
+    }
+}
+
+/// Splits a diff into per-file diffs.
+pub fn parse(diff: &str) -> Vec<FileDiff<'_>> {
+    let mut files: Vec<FileDiff> = Vec::new();
+    for line in diff.lines() {
+        if let Some(paths) = line.strip_prefix("diff --git ") {
+            let path = match paths.rfind(" b/") {
+                Some(i) => &paths[i + 3..],
+                None => paths,
+            };
+            files.push(FileDiff {
+                path: path.to_string(),
+                header: vec![line],
+                hunks: Vec::new(),
+                additions: 0,
+                deletions: 0,
+            });
+            continue;
+        }
+        let Some(file) = files.last_mut() else {
+            continue;
+        };
+        if line.starts_with("@@") {
+            file.hunks.push(Hunk {
+                header: line,
+                lines: Vec::new(),
+            });
+        } else if let Some(hunk) = file.hunks.last_mut() {
+            if line.starts_with('+') {
+                file.additions += 1;
+            } else if line.starts_with('-') {
+                file.deletions += 1;
+            }
+            hunk.lines.push(line);
+        } else {
+            file.header.push(line);
+        }
+    }
+    files
+}
+
+/// A diff that has been trimmed to fit a token budget.
+pub struct Trimmed {
+    pub text: String,
+    /// Human readable descriptions of anything that was left out.
+    pub notes: Vec<String>,
+    /// The number of files left out entirely.
+    pub omitted_files: usize,
+}
+
+/// Trims `diff` to roughly `budget` tokens.
+///
+/// File and hunk headers are kept while the bodies of large hunks are cut
+/// short. If that is not enough, the largest files are dropped entirely and
+/// listed with their line counts at the end instead.
+pub fn trim(diff: &str, budget: usize) -> Trimmed {
+    if estimate_tokens(diff) <= budget {
+        return Trimmed {
+            text: diff.to_string(),
+            notes: Vec::new(),
+            omitted_files: 0,
+        };
+    }
+
+    let mut files = parse(diff);
+    let mut dropped = Vec::new();
+    let mut notes = Vec::new();
+
+    // Drop the largest files until the headers alone fit.
+    while !files.is_empty() && estimate_tokens(&render(&files, 0, &dropped)) > budget {
+        let largest = (0..files.len())
+            .max_by_key(|&i| files[i].additions + files[i].deletions)
+            .unwrap();
+        let file = files.remove(largest);
+        notes.push(format!("omitted {}", file.stat()));
+        dropped.push(file);
+    }
+
+    // Find the most hunk lines per hunk that still fit.
+    let mut low = 0;
+    let mut high = files.iter().map(FileDiff::longest_hunk).max().unwrap_or(0);
+    while low < high {
+        let mid = (low + high).div_ceil(2);
+        if estimate_tokens(&render(&files, mid, &dropped)) <= budget {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+
+    let truncated_hunks: usize = files
+        .iter()
+        .flat_map(|file| &file.hunks)
+        .filter(|hunk| hunk.lines.len() > low)
+        .count();
+    if truncated_hunks > 0 {
+        notes.push(format!(
+            "truncated {} hunks to {} lines each",
+            truncated_hunks, low
+        ));
+    }
+
+    Trimmed {
+        text: render(&files, low, &dropped),
+        notes,
+        omitted_files: dropped.len(),
+    }
+}
+
+fn render(files: &[FileDiff], max_lines: usize, dropped: &[FileDiff]) -> String {
+    let mut out = String::new();
+    for file in files {
+        file.render(max_lines, &mut out);
+    }
+    if !dropped.is_empty() {
+        out.push_str("\nChanges to the following files were omitted:\n");
+        for file in dropped {
+            out.push_str(&file.stat());
+            out.push('\n');
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A diff adding `lines` lines to `path`, in a single hunk.
+    fn file_diff(path: &str, lines: usize) -> String {
+        let mut diff = format!(
+            "diff --git a/{path} b/{path}\nindex 0000000..1111111 100644\n--- a/{path}\n+++ b/{path}\n@@ -0,0 +1,{lines} @@\n"
+        );
+        for i in 0..lines {
+            diff.push_str(&format!("+line {}\n", i));
+        }
+        diff
+    }
+
+    #[test]
+    fn parse_counts_lines_per_file() {
+        let diff = format!("{}{}", file_diff("a.rs", 3), file_diff("dir/b.rs", 1));
+        let files = parse(&diff);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, "a.rs");
+        assert_eq!((files[0].additions, files[0].deletions), (3, 0));
+        assert_eq!(files[1].stat(), "dir/b.rs | +1 -0");
+        assert_eq!(files[1].change(), "updated");
+    }
+
+    #[test]
+    fn trim_keeps_a_diff_within_budget() {
+        let diff = file_diff("a.rs", 10);
+        let trimmed = trim(&diff, estimate_tokens(&diff));
+        assert_eq!(trimmed.text, diff);
+        assert!(trimmed.notes.is_empty());
+        assert_eq!(trimmed.omitted_files, 0);
+    }
+
+    #[test]
+    fn trim_keeps_as_many_hunk_lines_as_fit() {
+        let diff = file_diff("a.rs", 100);
+        let files = parse(&diff);
+        let budget = estimate_tokens(&render(&files, 40, &[]));
+
+        let trimmed = trim(&diff, budget);
+        assert_eq!(trimmed.text, render(&files, 40, &[]));
+        assert!(trimmed.text.contains("[... 60 more lines]"));
+        assert_eq!(trimmed.notes, ["truncated 1 hunks to 40 lines each"]);
+        assert_eq!(trimmed.omitted_files, 0);
+    }
+
+    #[test]
+    fn trim_drops_the_largest_files_when_headers_do_not_fit() {
+        let diff = format!(
+            "{}{}{}",
+            file_diff("small.rs", 1),
+            file_diff("large.rs", 50),
+            file_diff("medium.rs", 5)
+        );
+        let trimmed = trim(&diff, 60);
+        assert_eq!(trimmed.omitted_files, 2);
+        assert_eq!(
+            trimmed.notes[..2],
+            ["omitted large.rs | +50 -0", "omitted medium.rs | +5 -0"]
+        );
+        assert!(trimmed
+            .text
+            .starts_with("diff --git a/small.rs b/small.rs\n"));
+        assert!(trimmed.text.contains(
+            "Changes to the following files were omitted:\nlarge.rs | +50 -0\nmedium.rs | +5 -0\n"
+        ));
+        assert!(estimate_tokens(&trimmed.text) <= 60);
+    }
+}
@@ -0,0 +1,104 @@
+//! Leaves generated, lock and vendored files out of the prompt.
+
+use globset::{Glob, GlobSetBuilder};
+use std::collections::HashSet;
+use std::path::Path;
+use std::process::Command;
+
+use crate::config;
+use crate::diff::{self, FileDiff};
+
+/// Patterns excluded when none are configured. Patterns without a `/` match
+/// the file name in any directory.
+pub const DEFAULT_EXCLUDES: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "poetry.lock",
+    "Pipfile.lock",
+    "Gemfile.lock",
+    "composer.lock",
+    "go.sum",
+    "*.min.js",
+    "*.min.css",
+    "*.map",
+    "**/vendor/**",
+    "**/node_modules/**",
+];
+
+/// A diff with excluded files removed.
+pub struct Filtered {
+    pub diff: String,
+    /// One line per excluded file, e.g. `updated Cargo.lock (+120/-80)`.
+    pub notes: Vec<String>,
+}
+
+/// Removes files matching `patterns`, or marked `linguist-generated` or
+/// `-diff` in `.gitattributes`, from `diff`.
+pub fn filter(diff: &str, patterns: &[String]) -> Result<Filtered, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    let globs = builder.build()?;
+
+    let files = diff::parse(diff);
+    let generated = generated_paths(&files);
+
+    let mut filtered = Filtered {
+        diff: String::new(),
+        notes: Vec::new(),
+    };
+    for file in &files {
+        let name = Path::new(&file.path).file_name().unwrap_or_default();
+        if globs.is_match(&file.path) || globs.is_match(name) || generated.contains(&file.path) {
+            filtered.notes.push(format!(
+                "{} {} (+{}/-{})",
+                file.change(),
+                file.path,
+                file.additions,
+                file.deletions
+            ));
+        } else {
+            filtered.diff.push_str(&file.text());
+        }
+    }
+    Ok(filtered)
+}
+
+/// Asks git which files are marked as generated or not diffable.
+fn generated_paths(files: &[FileDiff]) -> HashSet<String> {
+    if files.is_empty() {
+        return HashSet::new();
+    }
+    // Diff paths are relative to the root, whereas `check-attr` resolves
+    // them against the current directory.
+    let Some(root) = config::repo_root() else {
+        return HashSet::new();
+    };
+    let output = Command::new("git")
+        .current_dir(root)
+        .args(["check-attr", "-z", "linguist-generated", "diff", "--"])
+        .args(files.iter().map(|file| &file.path))
+        .output();
+    let output = match output {
+        Ok(output) if output.status.success() => output,
+        _ => return HashSet::new(),
+    };
+
+    // `-z` output is a sequence of NUL terminated path, attribute, value
+    // triples.
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let fields: Vec<&str> = stdout.split('\0').collect();
+    fields
+        .chunks_exact(3)
+        .filter(|triple| match (triple[1], triple[2]) {
+            ("linguist-generated", value) => value == "set" || value == "true",
+            ("diff", value) => value == "unset",
+            _ => false,
+        })
+        .map(|triple| triple[0].to_string())
+        .collect()
+}
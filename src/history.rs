@@ -0,0 +1,59 @@
+//! Recent commit messages, used as examples of the repository's style.
+
+use std::process::Command;
+
+use crate::message;
+
+/// Separates messages in `git log` output, since bodies may contain blank
+/// lines.
+const SEPARATOR: char = '\0';
+
+/// The messages of the last `count` non-merge commits, or just their subjects
+/// unless `body` is set. Only commits touching `paths` are considered when
+/// any are given, falling back to all commits if none do.
+pub fn recent(count: usize, paths: &[String], body: bool) -> Vec<String> {
+    if count == 0 {
+        return Vec::new();
+    }
+    let messages = log(count, paths, body);
+    if messages.is_empty() && !paths.is_empty() {
+        log(count, &[], body)
+    } else {
+        messages
+    }
+}
+
+fn log(count: usize, paths: &[String], body: bool) -> Vec<String> {
+    let format = if body { "%B%x00" } else { "%s%x00" };
+    let output = Command::new("git")
+        .args(["--no-pager", "log", "--no-merges"])
+        .arg(format!("--max-count={}", count))
+        .arg(format!("--format={}", format))
+        .arg("--")
+        // The paths are relative to the root rather than the current
+        // directory.
+        .args(paths.iter().map(|path| format!(":(top){}", path)))
+        .output();
+    // A repository without commits has no history to learn from.
+    match output {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .split(SEPARATOR)
+            .map(str::trim)
+            .filter(|message| !message.is_empty())
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Instructions appended to the prompt asking the model to follow the style
+/// of `messages`, or an empty string when there are none.
+pub fn instructions(messages: &[String]) -> String {
+    if messages.is_empty() {
+        return String::new();
+    }
+    format!(
+        "Match the style of these recent commit messages from the same repository, e.g. tense, capitalisation, prefixes and ticket references, but not their content:\n\n{}",
+        message::list(messages)
+    )
+}
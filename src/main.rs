@@ -1,73 +1,183 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use config::Config;
 use console::{style, Term};
+use conventional::Conventional;
 use crossterm::{
     event::{self, Event, KeyCode, KeyEvent},
     terminal,
 };
 use indicatif::{ProgressBar, ProgressStyle};
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
-use std::process::Command;
+use provider::{ChatRequest, Error, Provider, ProviderKind, ProviderOptions, Usage};
+use redact::Redactor;
+use response::{Candidates, Suggestion};
+use std::collections::BTreeMap;
+use std::io::{IsTerminal, Write};
+use std::path::PathBuf;
+use std::process::{Command, ExitCode, ExitStatus, Stdio};
 use std::time::Duration;
+use template::{Template, Variables};
+use ticket::{Placement, Tickets};
 
-#[derive(Debug, Serialize, Deserialize)]
-struct OpenAIResponse {
-    choices: Vec<Choice>,
-}
+/// Tokens reserved for the model's response when sizing the diff, unless
+/// `max_tokens` is set.
+const RESPONSE_TOKENS: usize = 1024;
+/// How many times to request messages before settling for fewer than asked
+/// for.
+const ATTEMPTS: usize = 3;
+/// Lines of the body shown under the highlighted message.
+const PREVIEW_LINES: usize = 3;
+
+mod config;
+mod conventional;
+mod credentials;
+mod diff;
+mod exclude;
+mod history;
+mod message;
+mod provider;
+mod redact;
+mod response;
+mod scope;
+mod summarize;
+mod template;
+mod ticket;
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Choice {
-    index: i32,
-    message: Message,
-    finish_reason: String,
+/// The instructions describing the expected format of the messages.
+fn build_instructions(
+    conventional: Option<&Conventional>,
+    body: bool,
+    language: Option<&str>,
+) -> String {
+    let mut instructions = Vec::new();
+    if let Some(conventional) = conventional {
+        instructions.push(conventional.instructions());
+    }
+    instructions.push(if body {
+        "Return a subject line of no more than 50 characters, followed by a blank line and a body explaining what changed and why. Do not include anything else.".to_string()
+    } else {
+        "Return only a single line of text no more than 50 characters. Do not include an explanation.".to_string()
+    });
+    if let Some(language) = language {
+        instructions.push(format!("Write the commit message in {}.", language));
+    }
+    instructions.join("\n")
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Message {
-    role: String,
-    content: String,
+fn build_content(prompt: &str, diff: &str, instructions: &str, examples: &str) -> String {
+    let examples = if examples.is_empty() {
+        String::new()
+    } else {
+        format!("\n\n{}", examples)
+    };
+    format!(
+        "{}\n{}{}\n\n```\n{}\n```",
+        prompt, instructions, examples, diff
+    )
 }
 
+/// Requests `count` messages, asking for replacements for any that are
+/// invalid or duplicates.
 async fn get_suggested_commit_messages(
-    diff: &str,
-    prompt: &str,
-    model: &str,
-) -> Result<Vec<String>, reqwest::Error> {
-    let api_key = std::env::var("OPENAI_API_KEY").expect("OPENAI_API_KEY not found");
-    let client = Client::new();
-    let content = format!("{}\nReturn only a single line of text no more than 50 characters. Do not include an explanation.\n\n```\n{}\n```", prompt, diff);
-
-    let response = client
-        .post("https://api.openai.com/v1/chat/completions")
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .json(&serde_json::json!({
-            "model": model,
-            "messages": [
-                {"role": "system", "content": "You are a helpful assistant."},
-                {"role": "user", "content": content}
-            ],
-            "n": 5
-        }))
-        .send()
-        .await?
-        .json::<OpenAIResponse>()
-        .await?;
+    provider: &dyn Provider,
+    system: &str,
+    content: &str,
+    count: usize,
+    mut candidates: Candidates<'_>,
+) -> Result<(Vec<Suggestion>, Usage), Error> {
+    let schema = candidates.schema();
+    let mut usage = Usage::default();
+    let mut requested = 0;
+    for _ in 0..ATTEMPTS {
+        let missing = count.saturating_sub(candidates.len());
+        if missing == 0 {
+            break;
+        }
+        let response = provider
+            .complete(&ChatRequest {
+                system,
+                content,
+                n: missing,
+                schema: Some(&schema),
+                seed_offset: requested,
+            })
+            .await?;
+        requested += missing as u64;
+        for completion in &response.completions {
+            candidates.add(completion);
+        }
+        usage += response.usage;
+    }
+    Ok((candidates.into_suggestions(), usage))
+}
 
-    let messages = response
-        .choices
-        .into_iter()
-        .map(|choice| {
-            let s = choice.message.content.trim();
-            let result = if s.starts_with('"') && s.ends_with('"') {
-                &s[1..s.len() - 1]
-            } else {
-                &s
-            };
-            result.to_string()
-        })
-        .collect();
-    Ok(messages)
+/// The paths of the staged files, relative to the repository root.
+fn staged_paths() -> Vec<String> {
+    match Command::new("git")
+        .args(["--no-pager", "diff", "--staged", "--name-only"])
+        .output()
+    {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Asks a yes/no question on the terminal, defaulting to no.
+fn confirm(question: &str) -> bool {
+    eprint!("{} [y/N] ", question);
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim(), "y" | "Y" | "yes")
+}
+
+/// Commits with `message`, then opens the editor on it if `amend` is set.
+fn commit(message: &str, amend: bool) -> std::io::Result<()> {
+    // Read the message from stdin so multi-line messages keep their
+    // formatting.
+    let mut child = Command::new("git")
+        .args(["commit", "-F", "-"])
+        .stdin(Stdio::piped())
+        .spawn()?;
+    // Dropping stdin closes it, letting git go ahead.
+    let written = child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(message.as_bytes());
+    // A failed commit, e.g. from a hook, explains itself better than the
+    // broken pipe it leaves behind.
+    check_commit(child.wait()?)?;
+    written?;
+    if amend {
+        check_commit(Command::new("git").args(["commit", "--amend"]).status()?)?;
+    }
+    Ok(())
+}
+
+/// Reports a failed commit, for the process to exit with.
+fn exit_code(committed: std::io::Result<()>) -> ExitCode {
+    match committed {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn check_commit(status: ExitStatus) -> std::io::Result<()> {
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!(
+            "git commit failed with {}",
+            status
+        )))
+    }
 }
 
 fn select_commit_message(commit_messages: Vec<String>) -> Option<String> {
@@ -76,35 +186,39 @@ fn select_commit_message(commit_messages: Vec<String>) -> Option<String> {
 
     loop {
         println!("Select a commit message:");
+        let mut lines = 1;
         for (i, msg) in commit_messages.iter().enumerate() {
+            let (subject, body) = message::split(msg);
             if i == index {
-                println!("{} {}", style(">").bold().green(), msg);
+                println!("{} {}", style(">").bold().green(), subject);
+                let body_lines: Vec<&str> = body.lines().collect();
+                for line in body_lines.iter().take(PREVIEW_LINES) {
+                    println!("    {}", style(line).dim());
+                }
+                if body_lines.len() > PREVIEW_LINES {
+                    println!("    {}", style("...").dim());
+                    lines += 1;
+                }
+                lines += body_lines.len().min(PREVIEW_LINES);
             } else {
-                println!("  {}", msg);
+                println!("  {}", subject);
             }
+            lines += 1;
         }
 
         terminal::enable_raw_mode().unwrap();
         let key_event = event::read().unwrap();
         terminal::disable_raw_mode().unwrap();
-        term.clear_last_lines(commit_messages.len() + 1).unwrap();
+        term.clear_last_lines(lines).unwrap();
 
         match key_event {
             Event::Key(KeyEvent {
                 code: KeyCode::Up, ..
-            }) => {
-                if index > 0 {
-                    index -= 1;
-                }
-            }
+            }) => index = index.saturating_sub(1),
             Event::Key(KeyEvent {
                 code: KeyCode::Down,
                 ..
-            }) => {
-                if index < commit_messages.len() - 1 {
-                    index += 1;
-                }
-            }
+            }) if index < commit_messages.len() - 1 => index += 1,
             Event::Key(KeyEvent {
                 code: KeyCode::Enter,
                 ..
@@ -119,28 +233,250 @@ fn select_commit_message(commit_messages: Vec<String>) -> Option<String> {
     Some(commit_messages[index].clone())
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// One message per line, or separated by `---` lines when they have a
+    /// body
+    Text,
+    /// A JSON object with the messages and details of the request
+    Json,
+}
+
 #[derive(Parser)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    after_help = "Settings are also read from ~/.config/git-commit-gpt/config.toml, \
+                  .git-commit-gpt.toml in the repository root, commitgpt.* git config \
+                  keys and GIT_COMMIT_GPT_* environment variables, in increasing order \
+                  of precedence. \
+                  Command line flags take precedence over all of them."
+)]
 struct Arguments {
     /// Don't amend after committing
-    #[arg(long)]
+    #[arg(long, overrides_with = "amend")]
     no_amend: bool,
 
+    /// Amend after committing, overriding the config
+    #[arg(long, overrides_with = "no_amend")]
+    amend: bool,
+
     /// A custom prompt to prefix the git diff with
     #[arg(short, long)]
     prompt: Option<String>,
 
-    /// The OpenAI model to use
+    /// The model to use (the deployment name for Azure)
     #[arg(short, long)]
     model: Option<String>,
+
+    /// The LLM provider to generate commit messages with
+    #[arg(long, value_enum)]
+    provider: Option<ProviderKind>,
+
+    /// The base URL of the provider's API
+    #[arg(long)]
+    base_url: Option<String>,
+
+    /// The Azure OpenAI API version
+    #[arg(long)]
+    api_version: Option<String>,
+
+    /// A shell command that prints the provider's API key, e.g. `pass show
+    /// openai`
+    #[arg(long)]
+    api_key_command: Option<String>,
+
+    /// A file containing the provider's API key
+    #[arg(long)]
+    api_key_file: Option<PathBuf>,
+
+    /// The most tokens of diff to send to the model
+    #[arg(long)]
+    max_diff_tokens: Option<usize>,
+
+    /// A glob pattern of files to leave out of the prompt, replacing the
+    /// defaults (can be repeated)
+    #[arg(long)]
+    exclude: Vec<String>,
+
+    /// Send the diff without redacting secrets
+    #[arg(long)]
+    no_redact: bool,
+
+    /// Abort instead of redacting when the diff appears to contain secrets
+    #[arg(long)]
+    abort_on_secrets: bool,
+
+    /// Print the requests that would be sent without sending them
+    #[arg(long, visible_alias = "dry-run")]
+    show_prompt: bool,
+
+    /// Show the requests and ask for confirmation before sending them
+    #[arg(long)]
+    confirm: bool,
+
+    /// Generate messages in the Conventional Commits format
+    #[arg(long)]
+    conventional: bool,
+
+    /// Generate a body explaining the change along with the subject
+    #[arg(long)]
+    body: bool,
+
+    /// The number of recent commit messages to learn the repository's style
+    /// from, none by default
+    #[arg(long, value_name = "N")]
+    examples: Option<usize>,
+
+    /// Only learn the style from commits touching the staged files
+    #[arg(long)]
+    examples_from_paths: bool,
+
+    /// Where to insert issue keys found in the branch name
+    #[arg(long, value_enum, value_name = "PLACEMENT")]
+    ticket: Option<Placement>,
+
+    /// The name of, or path to, a prompt template to use instead of the
+    /// prompt and built-in instructions
+    #[arg(long, value_name = "NAME")]
+    template: Option<String>,
+
+    /// The language to write commit messages in
+    #[arg(long)]
+    language: Option<String>,
+
+    /// The system message sent with each request
+    #[arg(long)]
+    system_message: Option<String>,
+
+    /// The sampling temperature, lower values give more deterministic
+    /// suggestions
+    #[arg(long)]
+    temperature: Option<f64>,
+
+    /// The nucleus sampling probability mass
+    #[arg(long)]
+    top_p: Option<f64>,
+
+    /// The most tokens to generate per suggestion
+    #[arg(long)]
+    max_tokens: Option<u32>,
+
+    /// A seed making suggestions repeatable where the provider supports it
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Request messages as JSON even from providers not known to support it
+    #[arg(long, overrides_with = "no_structured_output")]
+    structured_output: bool,
+
+    /// Request messages as free text
+    #[arg(long, overrides_with = "structured_output")]
+    no_structured_output: bool,
+
+    /// The number of suggestions to generate, 1 commits with the suggestion
+    /// without showing the menu
+    #[arg(short = 'n', long, value_parser = clap::value_parser!(u64).range(1..))]
+    count: Option<u64>,
+
+    /// Print the suggestions instead of choosing one to commit with, the
+    /// default when not run from a terminal
+    #[arg(long, conflicts_with = "yes")]
+    print: bool,
+
+    /// Commit with the first suggestion without asking
+    #[arg(short, long)]
+    yes: bool,
+
+    /// How to print the suggestions
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    /// Discard suggestions with longer subject lines
+    #[arg(long, value_name = "CHARS")]
+    max_subject_length: Option<usize>,
+}
+
+impl From<Arguments> for Config {
+    fn from(args: Arguments) -> Self {
+        let structured_output = if args.no_structured_output {
+            Some(false)
+        } else if args.structured_output {
+            Some(true)
+        } else {
+            None
+        };
+        let amend = if args.no_amend {
+            Some(false)
+        } else if args.amend {
+            Some(true)
+        } else {
+            None
+        };
+        Config {
+            provider: args.provider,
+            model: args.model,
+            base_url: args.base_url,
+            api_version: args.api_version,
+            prompt: args.prompt,
+            count: args.count.map(|count| count as usize),
+            amend,
+            // Given for a single run, they're for whichever provider it uses.
+            api_key_command: args.api_key_command.map(config::for_every_provider),
+            api_key_file: args.api_key_file.map(config::for_every_provider),
+            max_diff_tokens: args.max_diff_tokens,
+            exclude: (!args.exclude.is_empty()).then_some(args.exclude),
+            redact: args.no_redact.then_some(false),
+            redact_abort: args.abort_on_secrets.then_some(true),
+            redact_disable: None,
+            redact_patterns: None,
+            confirm: args.confirm.then_some(true),
+            conventional: args.conventional.then_some(true),
+            conventional_types: None,
+            scopes: None,
+            body: args.body.then_some(true),
+            examples: args.examples,
+            examples_from_paths: args.examples_from_paths.then_some(true),
+            ticket_pattern: None,
+            ticket_placement: args.ticket,
+            ticket_template: None,
+            template: args.template,
+            language: args.language,
+            system_message: args.system_message,
+            temperature: args.temperature,
+            top_p: args.top_p,
+            max_tokens: args.max_tokens,
+            seed: args.seed,
+            structured_output,
+            max_subject_length: args.max_subject_length,
+        }
+    }
 }
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     let args = Arguments::parse();
+    let show_prompt = args.show_prompt;
+    let yes = args.yes;
+    let format = args.format;
+    // The menu needs a terminal to read keys from and draw on, so print the
+    // messages instead when run from a script or editor.
+    let interactive = !args.print
+        && matches!(format, Format::Text)
+        && std::io::stdin().is_terminal()
+        && std::io::stdout().is_terminal();
+    let config = match Config::load(args.into()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
 
     let git_diff_output = Command::new("git")
-        .args(&["--no-pager", "diff", "--staged"])
+        .args(["--no-pager", "diff", "--staged"])
         .output()
         .expect("Failed to execute git diff command");
 
@@ -149,7 +485,7 @@ async fn main() {
             "Error executing git diff command: {:?}",
             git_diff_output.status
         );
-        return;
+        return ExitCode::FAILURE;
     }
 
     let git_diff =
@@ -157,58 +493,363 @@ async fn main() {
 
     if git_diff.trim().is_empty() {
         eprintln!("no changes added to commit (use \"git add\" and/or \"git commit -a\")");
-        return;
+        return ExitCode::FAILURE;
     }
 
-    let pb = ProgressBar::new_spinner();
-    pb.enable_steady_tick(Duration::from_millis(100));
-    pb.set_style(ProgressStyle::with_template("{spinner:.green} {wide_msg}").unwrap());
-    pb.set_message("Fetching suggested commit messages...");
+    let provider = match config.provider().build(ProviderOptions {
+        model: config.model.clone(),
+        base_url: config.base_url.clone(),
+        api_version: config.api_version.clone(),
+        key_sources: config.key_sources(),
+        sampling: config.sampling(),
+        structured_output: config.structured_output,
+        dry_run: show_prompt,
+    }) {
+        Ok(provider) => provider,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut filtered = match exclude::filter(&git_diff, &config.exclude()) {
+        Ok(filtered) => filtered,
+        Err(e) => {
+            eprintln!("Error: invalid exclude pattern: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut redactions = BTreeMap::new();
+    if config.redact() {
+        let redactor = match Redactor::new(
+            config.redact_disable.as_deref().unwrap_or_default(),
+            config.redact_patterns.as_deref().unwrap_or_default(),
+        ) {
+            Ok(redactor) => redactor,
+            Err(e) => {
+                eprintln!("Error: invalid redaction pattern: {}", e);
+                return ExitCode::FAILURE;
+            }
+        };
+        let redacted = redactor.redact(&filtered.diff);
+        if !redacted.findings.is_empty() {
+            let findings = redacted
+                .findings
+                .iter()
+                .map(|(rule, count)| format!("{} ({})", rule, count))
+                .collect::<Vec<_>>()
+                .join(", ");
+            if config.redact_abort() {
+                eprintln!(
+                    "Error: the staged changes appear to contain secrets: {}",
+                    findings
+                );
+                return ExitCode::FAILURE;
+            }
+            eprintln!(
+                "{}",
+                style(format!("Redacted possible secrets: {}", findings)).dim()
+            );
+        }
+        filtered.diff = redacted.text;
+        redactions = redacted.findings;
+    }
 
-    let prompt = match args.prompt {
-        Some(prompt) => prompt,
-        None => "Given the following git diff, suggest a commit message that can be passed to `git commit`.".to_string()
+    // Excluded files are listed after the rest of the diff.
+    let excluded = if filtered.notes.is_empty() {
+        String::new()
+    } else {
+        format!("\n{}\n", filtered.notes.join("\n"))
     };
 
-    let model = match args.model {
-        Some(model) => model,
-        None => "gpt-3.5-turbo".to_string(),
+    let tickets = match Tickets::from_branch(
+        config.ticket_pattern(),
+        config.ticket_placement(),
+        config.ticket_template.as_deref(),
+    ) {
+        Ok(tickets) => tickets,
+        Err(e) => {
+            eprintln!("Error: invalid ticket pattern: {}", e);
+            return ExitCode::FAILURE;
+        }
     };
 
-    let commit_messages_result = get_suggested_commit_messages(&git_diff, &prompt, &model).await;
+    let template = match config.template.as_deref().map(Template::load).transpose() {
+        Ok(template) => template,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let paths = staged_paths();
+    let recent = history::recent(
+        config.examples(),
+        if config.examples_from_paths() {
+            &paths
+        } else {
+            &[]
+        },
+        config.body(),
+    );
+    let examples = history::instructions(&recent);
+    let recent_commits = message::list(&recent);
+
+    let conventional = config.conventional().then(|| {
+        let scopes = match config::repo_root() {
+            Some(root) => scope::infer(&paths, &root, &config.scopes.clone().unwrap_or_default()),
+            None => Vec::new(),
+        };
+        Conventional::new(config.conventional_types(), scopes)
+    });
+    let instructions = build_instructions(
+        conventional.as_ref(),
+        config.body(),
+        config.language.as_deref(),
+    );
+    let stat = diff::parse(&git_diff)
+        .iter()
+        .map(|file| file.stat())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let branch = config::current_branch().unwrap_or_default();
+    let files = paths.join("\n");
+    let render = |diff: &str| match &template {
+        Some(template) => template.render(&Variables {
+            diff,
+            stat: &stat,
+            branch: &branch,
+            files: &files,
+            recent_commits: &recent_commits,
+            language: config.language(),
+            instructions: &instructions,
+        }),
+        None => build_content(config.prompt(), diff, &instructions, &examples),
+    };
+
+    let budget = config.max_diff_tokens.unwrap_or_else(|| {
+        provider.context_window().saturating_sub(
+            diff::estimate_tokens(&render(""))
+                + config
+                    .max_tokens
+                    .map_or(RESPONSE_TOKENS, |max_tokens| max_tokens as usize),
+        )
+    });
+    let budget = budget.saturating_sub(diff::estimate_tokens(&excluded));
+    let trimmed = diff::trim(&filtered.diff, budget);
+    // Files only get dropped when even their headers don't fit, in which case
+    // each file is summarised separately instead.
+    let summarize = trimmed.omitted_files > 0;
+    let file_requests = summarize.then(|| summarize::file_requests(&filtered.diff, budget));
+    if let Some(file_requests) = &file_requests {
+        let summarised = file_requests.requests.len();
+        let files = if file_requests.unsummarised.is_empty() {
+            "each file".to_string()
+        } else {
+            format!(
+                "the {} largest of {} files",
+                summarised,
+                summarised + file_requests.unsummarised.len()
+            )
+        };
+        let note = format!(
+            "Diff too large for the model's context, sending {} requests to summarise {} first",
+            summarised, files
+        );
+        eprintln!("{}", style(note).dim());
+    } else if !trimmed.notes.is_empty() {
+        eprintln!(
+            "{}",
+            style(format!(
+                "Diff trimmed to fit the model's context: {}",
+                trimmed.notes.join(", ")
+            ))
+            .dim()
+        );
+    }
+    let content = render(&format!("{}{}", trimmed.text, excluded));
+
+    if show_prompt || config.confirm() {
+        let schema = response::schema(conventional.is_some(), config.body());
+        // Only the requests carrying the diff itself, when summarising the
+        // final request just contains the model's summaries.
+        let requests: Vec<ChatRequest> = if let Some(file_requests) = &file_requests {
+            file_requests
+                .requests
+                .iter()
+                .map(|request| ChatRequest {
+                    system: config.system_message(),
+                    content: &request.content,
+                    n: 1,
+                    schema: None,
+                    seed_offset: 0,
+                })
+                .collect()
+        } else {
+            vec![ChatRequest {
+                system: config.system_message(),
+                content: &content,
+                n: config.count(),
+                schema: Some(&schema),
+                seed_offset: 0,
+            }]
+        };
+        for request in &requests {
+            match provider.payload(request).await {
+                Ok(payload) => println!("{}\n", payload),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        if summarize {
+            println!("The commit messages are then requested from the summaries returned by the requests above.\n");
+        }
+
+        if show_prompt || !confirm("Send these requests?") {
+            return ExitCode::SUCCESS;
+        }
+    }
+
+    let pb = ProgressBar::new_spinner();
+    pb.enable_steady_tick(Duration::from_millis(100));
+    pb.set_style(ProgressStyle::with_template("{spinner:.green} {wide_msg}").unwrap());
+    pb.set_message("Fetching suggested commit messages...");
+
+    let commit_messages_result = async {
+        let mut usage = Usage::default();
+        let content = if let Some(file_requests) = &file_requests {
+            let (summaries, summary_usage) = summarize::summarize(
+                provider.as_ref(),
+                config.system_message(),
+                file_requests,
+                &pb,
+            )
+            .await?;
+            usage += summary_usage;
+            render(&format!("{}{}", summaries, excluded))
+        } else {
+            content
+        };
+        let (suggestions, suggestion_usage) = get_suggested_commit_messages(
+            provider.as_ref(),
+            config.system_message(),
+            &content,
+            config.count(),
+            Candidates::new(
+                conventional.as_ref(),
+                config.body(),
+                config.max_subject_length(),
+                &tickets,
+            ),
+        )
+        .await?;
+        usage += suggestion_usage;
+        Ok::<_, Error>((suggestions, usage))
+    }
+    .await;
 
     pb.finish_and_clear();
 
     match commit_messages_result {
-        Ok(commit_messages) => {
+        Ok((suggestions, usage)) => {
+            let commit_messages: Vec<String> = suggestions
+                .iter()
+                .map(|suggestion| suggestion.message.clone())
+                .collect();
+            if commit_messages.is_empty() {
+                let note = if conventional.is_some() {
+                    "None of the suggestions were valid Conventional Commits messages"
+                } else {
+                    "None of the suggestions were valid"
+                };
+                eprintln!("{}", style(note).dim());
+            }
+            if yes {
+                return match commit_messages.first() {
+                    // There's no one to edit the message, so don't amend.
+                    Some(message) => exit_code(commit(message, false)),
+                    None => {
+                        eprintln!("Error: no commit message to commit with");
+                        ExitCode::FAILURE
+                    }
+                };
+            }
+            // With a single suggestion there's nothing to choose between.
+            if interactive && config.count() == 1 {
+                if let [message] = commit_messages.as_slice() {
+                    return exit_code(commit(message, config.amend()));
+                }
+            }
+            if !interactive {
+                match format {
+                    Format::Text => println!("{}", message::list(&commit_messages)),
+                    Format::Json => {
+                        let files = diff::parse(&git_diff);
+                        let output = serde_json::json!({
+                            "provider": config.provider().to_possible_value().unwrap().get_name(),
+                            "model": provider.model(),
+                            "candidates": suggestions
+                                .iter()
+                                .map(|suggestion| {
+                                    let (subject, body) = message::split(&suggestion.message);
+                                    serde_json::json!({
+                                        "message": suggestion.message,
+                                        "subject": subject,
+                                        "body": body,
+                                        "finish_reason": suggestion.finish_reason,
+                                    })
+                                })
+                                .collect::<Vec<_>>(),
+                            "usage": usage,
+                            "diff": {
+                                "files": files
+                                    .iter()
+                                    .map(|file| serde_json::json!({
+                                        "path": file.path,
+                                        "change": file.change(),
+                                        "additions": file.additions,
+                                        "deletions": file.deletions,
+                                    }))
+                                    .collect::<Vec<_>>(),
+                                "additions": files.iter().map(|file| file.additions).sum::<usize>(),
+                                "deletions": files.iter().map(|file| file.deletions).sum::<usize>(),
+                            },
+                            "excluded": filtered.notes,
+                            "redacted": redactions,
+                            "trimmed": trimmed.notes,
+                            "summarised": summarize,
+                        });
+                        println!("{}", serde_json::to_string_pretty(&output).unwrap());
+                    }
+                }
+                // Scripts can't use an empty list of messages.
+                return if commit_messages.is_empty() {
+                    ExitCode::FAILURE
+                } else {
+                    ExitCode::SUCCESS
+                };
+            }
+
             let mut options = vec!["Enter a custom message...".to_string()];
             options.extend(commit_messages.iter().cloned());
             if let Some(selected_message) = select_commit_message(options) {
-                if selected_message == "Enter a custom message..." {
+                let committed = if selected_message == "Enter a custom message..." {
                     Command::new("git")
                         .args(["commit"])
-                        .spawn()
-                        .unwrap()
-                        .wait()
-                        .unwrap();
+                        .status()
+                        .and_then(check_commit)
                 } else {
-                    Command::new("git")
-                        .args(["commit", "-m", &selected_message])
-                        .spawn()
-                        .unwrap()
-                        .wait()
-                        .unwrap();
-                    if !args.no_amend {
-                        Command::new("git")
-                            .args(["commit", "--amend"])
-                            .spawn()
-                            .unwrap()
-                            .wait()
-                            .unwrap();
-                    }
-                }
+                    commit(&selected_message, config.amend())
+                };
+                return exit_code(committed);
             }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
         }
-        Err(e) => eprintln!("Error: {}", e),
     }
 }
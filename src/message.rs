@@ -0,0 +1,150 @@
+//! Commit message formatting.
+
+/// The column commit message bodies are wrapped at.
+pub const BODY_WIDTH: usize = 72;
+
+/// Splits a message into its subject line and body.
+pub fn split(message: &str) -> (&str, &str) {
+    let message = message.trim();
+    match message.split_once('\n') {
+        Some((subject, body)) => (subject.trim(), body.trim()),
+        None => (message, ""),
+    }
+}
+
+/// Joins a subject and body with the blank line git expects between them.
+pub fn join(subject: &str, body: &str) -> String {
+    if body.is_empty() {
+        subject.to_string()
+    } else {
+        format!("{}\n\n{}", subject, body)
+    }
+}
+
+/// Lists `messages`, one per line or separated by `---` when any has a body.
+pub fn list(messages: &[String]) -> String {
+    // Messages with bodies need a clearer boundary than a line break.
+    let separator = if messages.iter().any(|message| message.contains('\n')) {
+        "\n---\n"
+    } else {
+        "\n"
+    };
+    messages.join(separator)
+}
+
+/// Rewraps each paragraph of `text` to `width` columns. List items are
+/// wrapped separately with their continuation lines indented.
+pub fn wrap(text: &str, width: usize) -> String {
+    let mut paragraphs = Vec::new();
+    for paragraph in text.split("\n\n") {
+        let mut items: Vec<String> = Vec::new();
+        for line in paragraph.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            match items.last_mut() {
+                Some(item) if list_marker(line).is_none() => {
+                    item.push(' ');
+                    item.push_str(line);
+                }
+                _ => items.push(line.to_string()),
+            }
+        }
+        if !items.is_empty() {
+            paragraphs.push(
+                items
+                    .iter()
+                    .map(|item| wrap_item(item, width))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            );
+        }
+    }
+    paragraphs.join("\n\n")
+}
+
+/// The list marker at the start of `line`, e.g. `- ` or `1. `.
+fn list_marker(line: &str) -> Option<&str> {
+    if line.starts_with("- ") || line.starts_with("* ") {
+        return Some(&line[..2]);
+    }
+    let digits = line.bytes().take_while(u8::is_ascii_digit).count();
+    if digits > 0 && line[digits..].starts_with(". ") {
+        return Some(&line[..digits + 2]);
+    }
+    None
+}
+
+fn wrap_item(item: &str, width: usize) -> String {
+    let indent = " ".repeat(list_marker(item).map_or(0, str::len));
+    let mut out = String::new();
+    // Counted in characters rather than bytes, for non-ASCII languages.
+    let mut column = 0;
+    for word in item.split_whitespace() {
+        let length = word.chars().count();
+        if column > 0 && column + 1 + length > width {
+            out.push('\n');
+            out.push_str(&indent);
+            column = indent.len();
+        } else if column > 0 {
+            out.push(' ');
+            column += 1;
+        }
+        out.push_str(word);
+        column += length;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap_fills_lines_up_to_the_width() {
+        assert_eq!(
+            wrap("one two three four five six", 13),
+            "one two three\nfour five six"
+        );
+        // Lines broken early by the model are rejoined.
+        assert_eq!(wrap("one two\nthree\nfour", 20), "one two three four");
+    }
+
+    #[test]
+    fn wrap_keeps_paragraphs_apart() {
+        assert_eq!(
+            wrap("First paragraph\n\n\nSecond\nparagraph", 72),
+            "First paragraph\n\nSecond paragraph"
+        );
+    }
+
+    #[test]
+    fn wrap_indents_list_items() {
+        assert_eq!(
+            wrap("Changes:\n- add the parser for\n  arrays\n12. fix it", 16),
+            "Changes:\n- add the parser\n  for arrays\n12. fix it"
+        );
+    }
+
+    #[test]
+    fn wrap_counts_characters() {
+        assert_eq!(wrap("änderung über größe", 19), "änderung über größe");
+    }
+
+    #[test]
+    fn wrap_leaves_long_words_whole() {
+        let url = "https://example.com/a/very/long/path";
+        assert_eq!(
+            wrap(&format!("See {} now", url), 10),
+            format!("See\n{}\nnow", url)
+        );
+    }
+
+    #[test]
+    fn split_and_join_round_trip() {
+        assert_eq!(
+            split("  Subject\n\nBody\nmore\n"),
+            ("Subject", "Body\nmore")
+        );
+        assert_eq!(split("Subject"), ("Subject", ""));
+        assert_eq!(join("Subject", "Body"), "Subject\n\nBody");
+        assert_eq!(join("Subject", ""), "Subject");
+    }
+}
@@ -0,0 +1,219 @@
+use async_trait::async_trait;
+use clap::ValueEnum;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::AddAssign;
+
+use crate::credentials::KeySources;
+
+mod anthropic;
+mod ollama;
+mod openai;
+
+pub use anthropic::Anthropic;
+pub use ollama::Ollama;
+pub use openai::OpenAI;
+
+/// A single chat request sent to a provider.
+pub struct ChatRequest<'a> {
+    pub system: &'a str,
+    pub content: &'a str,
+    pub n: usize,
+    /// A JSON schema for the completions to follow, when the provider
+    /// supports structured output. Completions are then JSON text.
+    pub schema: Option<&'a serde_json::Value>,
+    /// Added to the seed, so that requests for replacements don't just
+    /// return the same completions again.
+    pub seed_offset: u64,
+}
+
+/// A single candidate returned by a provider.
+pub struct Completion {
+    pub text: String,
+    /// Why the model stopped, e.g. `stop` or `length`, when reported.
+    pub finish_reason: Option<String>,
+}
+
+/// Tokens used by one or more requests, as reported by the provider.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, other: Usage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+    }
+}
+
+/// The completions for a chat request, possibly gathered from several HTTP
+/// requests.
+#[derive(Default)]
+pub struct Response {
+    pub completions: Vec<Completion>,
+    pub usage: Usage,
+}
+
+impl FromIterator<Response> for Response {
+    fn from_iter<I: IntoIterator<Item = Response>>(responses: I) -> Self {
+        let mut combined = Response::default();
+        for response in responses {
+            combined.completions.extend(response.completions);
+            combined.usage += response.usage;
+        }
+        combined
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Request(reqwest::Error),
+    Provider(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Request(e) => write!(f, "{}", e),
+            Error::Provider(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Request(e)
+    }
+}
+
+/// Reads a JSON response, turning an error status into an error carrying the
+/// provider's own message rather than failing to decode the body.
+async fn read_json<T: DeserializeOwned>(response: reqwest::Response) -> Result<T, Error> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response.json().await?);
+    }
+    let body = response.text().await.unwrap_or_default();
+    Err(Error::Provider(match error_message(&body) {
+        Some(message) => format!("{} ({})", message, status),
+        None => format!("request failed with status {}", status),
+    }))
+}
+
+/// The message in an error response body. OpenAI and Anthropic nest it in an
+/// `error` object while Ollama returns it as the `error` string.
+fn error_message(body: &str) -> Option<String> {
+    let value: serde_json::Value = match serde_json::from_str(body) {
+        Ok(value) => value,
+        Err(_) => return Some(body.trim().to_string()).filter(|body| !body.is_empty()),
+    };
+    match &value["error"] {
+        serde_json::Value::String(message) => Some(message.clone()),
+        error => error["message"].as_str().map(str::to_string),
+    }
+}
+
+/// An HTTP request as it would be sent to a provider.
+pub struct Payload {
+    pub url: String,
+    pub body: serde_json::Value,
+    /// How many times the request is sent, for providers that return a
+    /// single completion per request.
+    pub times: usize,
+}
+
+impl fmt::Display for Payload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "POST {}", self.url)?;
+        if self.times > 1 {
+            write!(f, " (sent {} times)", self.times)?;
+        }
+        let body = serde_json::to_string_pretty(&self.body).map_err(|_| fmt::Error)?;
+        write!(f, "\n{}", body)
+    }
+}
+
+/// A backend capable of generating chat completions.
+#[async_trait]
+pub trait Provider {
+    /// Returns `request.n` candidate completions for the request.
+    async fn complete(&self, request: &ChatRequest<'_>) -> Result<Response, Error>;
+
+    /// Describes what `complete` would send for the request, without
+    /// sending it.
+    async fn payload(&self, request: &ChatRequest<'_>) -> Result<Payload, Error>;
+
+    /// The number of tokens the model accepts, including the response.
+    fn context_window(&self) -> usize;
+
+    /// The model requests are sent to, once known.
+    fn model(&self) -> &str;
+}
+
+/// Settings used to construct a provider.
+pub struct ProviderOptions {
+    /// The model to use, or the provider's default when `None`.
+    pub model: Option<String>,
+    /// Overrides the provider's default API endpoint.
+    pub base_url: Option<String>,
+    /// The Azure OpenAI `api-version` query parameter.
+    pub api_version: Option<String>,
+    /// Where to look for an API key besides the provider's environment
+    /// variable.
+    pub key_sources: KeySources,
+    pub sampling: Sampling,
+    /// Whether to request structured output, or the provider's default when
+    /// `None`.
+    pub structured_output: Option<bool>,
+    /// Only build payloads to show, so no API key is looked up and nothing
+    /// is sent.
+    pub dry_run: bool,
+}
+
+/// Sampling parameters sent with each request. Providers use their own
+/// defaults for any left unset.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sampling {
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    /// The most tokens to generate per completion.
+    pub max_tokens: Option<u32>,
+    /// Makes sampling repeatable where the provider supports it.
+    pub seed: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    #[value(name = "openai")]
+    OpenAI,
+    Ollama,
+    Anthropic,
+    Azure,
+}
+
+impl ProviderKind {
+    /// The name used on the command line and in config, e.g. `openai`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ProviderKind::OpenAI => "openai",
+            ProviderKind::Ollama => "ollama",
+            ProviderKind::Anthropic => "anthropic",
+            ProviderKind::Azure => "azure",
+        }
+    }
+
+    pub fn build(self, options: ProviderOptions) -> Result<Box<dyn Provider>, Error> {
+        Ok(match self {
+            ProviderKind::OpenAI => Box::new(OpenAI::new(options)?),
+            ProviderKind::Ollama => Box::new(Ollama::new(options)),
+            ProviderKind::Anthropic => Box::new(Anthropic::new(options)?),
+            ProviderKind::Azure => Box::new(OpenAI::azure(options)?),
+        })
+    }
+}
@@ -0,0 +1,166 @@
+use async_trait::async_trait;
+use futures::future::try_join_all;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    read_json, ChatRequest, Completion, Error, Payload, Provider, ProviderOptions, Response,
+    Sampling, Usage,
+};
+
+const DEFAULT_BASE_URL: &str = "https://api.anthropic.com";
+const DEFAULT_MODEL: &str = "claude-3-5-haiku-latest";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+const MAX_TOKENS: u32 = 1024;
+const CONTEXT_WINDOW: usize = 200_000;
+/// The tool structured output is requested through.
+const TOOL_NAME: &str = "commit_message";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MessagesResponse {
+    content: Vec<ContentBlock>,
+    stop_reason: Option<String>,
+    usage: MessagesUsage,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MessagesUsage {
+    input_tokens: u64,
+    output_tokens: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentBlock {
+    Text {
+        text: String,
+    },
+    ToolUse {
+        input: serde_json::Value,
+    },
+    #[serde(other)]
+    Other,
+}
+
+pub struct Anthropic {
+    client: Client,
+    api_key: String,
+    model: String,
+    base_url: String,
+    sampling: Sampling,
+    structured_output: bool,
+}
+
+impl Anthropic {
+    pub fn new(options: ProviderOptions) -> Result<Self, Error> {
+        let api_key = if options.dry_run {
+            String::new()
+        } else {
+            options
+                .key_sources
+                .require("ANTHROPIC_API_KEY", "anthropic")?
+        };
+        let base_url = options
+            .base_url
+            .unwrap_or_else(|| DEFAULT_BASE_URL.to_string());
+        Ok(Self {
+            client: Client::new(),
+            api_key,
+            model: options.model.unwrap_or_else(|| DEFAULT_MODEL.to_string()),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            sampling: options.sampling,
+            structured_output: options.structured_output.unwrap_or(true),
+        })
+    }
+
+    fn url(&self) -> String {
+        format!("{}/v1/messages", self.base_url)
+    }
+
+    /// The Messages API has no seed, so `sampling.seed` is ignored.
+    fn body(&self, request: &ChatRequest<'_>) -> serde_json::Value {
+        let mut body = serde_json::json!({
+            "model": self.model,
+            "max_tokens": self.sampling.max_tokens.unwrap_or(MAX_TOKENS),
+            "system": request.system,
+            "messages": [
+                {"role": "user", "content": request.content}
+            ]
+        });
+        if let Some(temperature) = self.sampling.temperature {
+            body["temperature"] = temperature.into();
+        }
+        if let Some(top_p) = self.sampling.top_p {
+            body["top_p"] = top_p.into();
+        }
+        // Structured output is done by forcing a call to a tool whose input
+        // follows the schema.
+        if let (Some(schema), true) = (request.schema, self.structured_output) {
+            body["tools"] = serde_json::json!([{
+                "name": TOOL_NAME,
+                "description": "Records the commit message.",
+                "input_schema": schema
+            }]);
+            body["tool_choice"] = serde_json::json!({"type": "tool", "name": TOOL_NAME});
+        }
+        body
+    }
+
+    async fn request(&self, request: &ChatRequest<'_>) -> Result<Response, Error> {
+        let response = self
+            .client
+            .post(self.url())
+            .header("Content-Type", "application/json")
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .json(&self.body(request))
+            .send()
+            .await?;
+        let response: MessagesResponse = read_json(response).await?;
+
+        let text = response
+            .content
+            .into_iter()
+            .filter_map(|block| match block {
+                ContentBlock::Text { text } => Some(text),
+                ContentBlock::ToolUse { input } => Some(input.to_string()),
+                ContentBlock::Other => None,
+            })
+            .collect();
+        Ok(Response {
+            completions: vec![Completion {
+                text,
+                finish_reason: response.stop_reason,
+            }],
+            usage: Usage {
+                prompt_tokens: response.usage.input_tokens,
+                completion_tokens: response.usage.output_tokens,
+            },
+        })
+    }
+}
+
+#[async_trait]
+impl Provider for Anthropic {
+    async fn complete(&self, request: &ChatRequest<'_>) -> Result<Response, Error> {
+        // The Messages API returns a single response per request.
+        let responses = try_join_all((0..request.n).map(|_| self.request(request))).await?;
+        Ok(responses.into_iter().collect())
+    }
+
+    async fn payload(&self, request: &ChatRequest<'_>) -> Result<Payload, Error> {
+        Ok(Payload {
+            url: self.url(),
+            body: self.body(request),
+            times: request.n,
+        })
+    }
+
+    fn context_window(&self) -> usize {
+        CONTEXT_WINDOW
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+}
@@ -0,0 +1,229 @@
+use async_trait::async_trait;
+use futures::future::try_join_all;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::OnceCell;
+
+use super::{
+    read_json, ChatRequest, Completion, Error, Payload, Provider, ProviderOptions, Response,
+    Sampling, Usage,
+};
+
+const DEFAULT_BASE_URL: &str = "http://localhost:11434";
+/// Requested explicitly since Ollama silently truncates longer prompts.
+const CONTEXT_WINDOW: usize = 4096;
+/// Stands in for the model in payloads when none was requested.
+const FIRST_AVAILABLE_MODEL: &str = "<first available model>";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ChatResponse {
+    message: Message,
+    done_reason: Option<String>,
+    #[serde(default)]
+    prompt_eval_count: u64,
+    #[serde(default)]
+    eval_count: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Message {
+    role: String,
+    content: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TagsResponse {
+    models: Vec<Model>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Model {
+    name: String,
+}
+
+pub struct Ollama {
+    client: Client,
+    base_url: String,
+    requested_model: Option<String>,
+    model: OnceCell<String>,
+    sampling: Sampling,
+    structured_output: bool,
+}
+
+impl Ollama {
+    pub fn new(options: ProviderOptions) -> Self {
+        let base_url = options
+            .base_url
+            .or_else(|| std::env::var("OLLAMA_HOST").ok())
+            .unwrap_or_else(|| DEFAULT_BASE_URL.to_string());
+        // Ollama itself accepts `OLLAMA_HOST` without a scheme, e.g.
+        // `127.0.0.1:11434`.
+        let base_url = if base_url.contains("://") {
+            base_url
+        } else {
+            format!("http://{}", base_url)
+        };
+        Self {
+            client: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            requested_model: options.model,
+            model: OnceCell::new(),
+            sampling: options.sampling,
+            structured_output: options.structured_output.unwrap_or(true),
+        }
+    }
+
+    /// Resolves the model against the ones pulled locally, falling back to
+    /// the first available model when none was requested.
+    async fn model(&self) -> Result<&str, Error> {
+        let model = self
+            .model
+            .get_or_try_init(|| async {
+                let tags = self
+                    .client
+                    .get(format!("{}/api/tags", self.base_url))
+                    .send()
+                    .await?;
+                let tags: TagsResponse = read_json(tags).await?;
+                let names: Vec<String> = tags.models.into_iter().map(|m| m.name).collect();
+
+                match &self.requested_model {
+                    Some(requested) => names
+                        .iter()
+                        .find(|name| {
+                            *name == requested || **name == format!("{}:latest", requested)
+                        })
+                        .cloned()
+                        .ok_or_else(|| {
+                            Error::Provider(format!(
+                                "model '{}' not found, try pulling it with `ollama pull {}` (available: {})",
+                                requested,
+                                requested,
+                                names.join(", ")
+                            ))
+                        }),
+                    None => names.into_iter().next().ok_or_else(|| {
+                        Error::Provider(
+                            "no Ollama models found, pull one with `ollama pull <model>`"
+                                .to_string(),
+                        )
+                    }),
+                }
+            })
+            .await?;
+        Ok(model)
+    }
+
+    fn url(&self) -> String {
+        format!("{}/api/chat", self.base_url)
+    }
+
+    fn body(&self, model: &str, request: &ChatRequest<'_>, seed: u64) -> serde_json::Value {
+        let mut options = serde_json::json!({"seed": seed, "num_ctx": CONTEXT_WINDOW});
+        if let Some(temperature) = self.sampling.temperature {
+            options["temperature"] = temperature.into();
+        }
+        if let Some(top_p) = self.sampling.top_p {
+            options["top_p"] = top_p.into();
+        }
+        if let Some(max_tokens) = self.sampling.max_tokens {
+            options["num_predict"] = max_tokens.into();
+        }
+        let mut body = serde_json::json!({
+            "model": model,
+            "messages": [
+                {"role": "system", "content": request.system},
+                {"role": "user", "content": request.content}
+            ],
+            "stream": false,
+            "options": options
+        });
+        if let (Some(schema), true) = (request.schema, self.structured_output) {
+            body["format"] = schema.clone();
+        }
+        body
+    }
+
+    /// The configured seed, or one that varies between runs so repeated
+    /// invocations give new suggestions.
+    fn base_seed(&self) -> u64 {
+        self.sampling.seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default()
+        })
+    }
+
+    async fn request(
+        &self,
+        model: &str,
+        request: &ChatRequest<'_>,
+        seed: u64,
+    ) -> Result<Response, Error> {
+        let response = self
+            .client
+            .post(self.url())
+            .json(&self.body(model, request, seed))
+            .send()
+            .await?;
+        let response: ChatResponse = read_json(response).await?;
+
+        Ok(Response {
+            completions: vec![Completion {
+                text: response.message.content,
+                finish_reason: response.done_reason,
+            }],
+            usage: Usage {
+                prompt_tokens: response.prompt_eval_count,
+                completion_tokens: response.eval_count,
+            },
+        })
+    }
+}
+
+#[async_trait]
+impl Provider for Ollama {
+    async fn complete(&self, request: &ChatRequest<'_>) -> Result<Response, Error> {
+        let model = self.model().await?;
+
+        // Ollama has no equivalent of `n`, so sample once per candidate with
+        // a different seed for each.
+        let base_seed = self.base_seed().wrapping_add(request.seed_offset);
+        let responses = try_join_all(
+            (0..request.n as u64).map(|i| self.request(model, request, base_seed.wrapping_add(i))),
+        )
+        .await?;
+        Ok(responses.into_iter().collect())
+    }
+
+    async fn payload(&self, request: &ChatRequest<'_>) -> Result<Payload, Error> {
+        // Resolving the model would contact the server, so the requested
+        // name is shown as is.
+        let model = self
+            .requested_model
+            .as_deref()
+            .unwrap_or(FIRST_AVAILABLE_MODEL);
+        Ok(Payload {
+            url: self.url(),
+            body: self.body(
+                model,
+                request,
+                self.base_seed().wrapping_add(request.seed_offset),
+            ),
+            times: request.n,
+        })
+    }
+
+    fn context_window(&self) -> usize {
+        CONTEXT_WINDOW
+    }
+
+    fn model(&self) -> &str {
+        self.model
+            .get()
+            .or(self.requested_model.as_ref())
+            .map_or("", String::as_str)
+    }
+}
@@ -0,0 +1,270 @@
+use async_trait::async_trait;
+use futures::future::try_join_all;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    read_json, ChatRequest, Completion, Error, Payload, Provider, ProviderOptions, Response,
+    Sampling, Usage,
+};
+
+const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+const DEFAULT_MODEL: &str = "gpt-3.5-turbo";
+const DEFAULT_AZURE_API_VERSION: &str = "2024-02-01";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OpenAIResponse {
+    choices: Vec<Choice>,
+    /// Not reported by every compatible server.
+    usage: Option<OpenAIUsage>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OpenAIUsage {
+    prompt_tokens: u64,
+    completion_tokens: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Choice {
+    index: i32,
+    message: Message,
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Message {
+    role: String,
+    content: String,
+}
+
+enum Auth {
+    None,
+    Bearer(String),
+    /// Azure's `api-key` header.
+    ApiKey(String),
+}
+
+/// A chat-completions API, either OpenAI's own, an OpenAI-compatible server
+/// or an Azure OpenAI deployment.
+pub struct OpenAI {
+    client: Client,
+    auth: Auth,
+    model: String,
+    url: String,
+    sampling: Sampling,
+    structured_output: bool,
+}
+
+impl OpenAI {
+    pub fn new(options: ProviderOptions) -> Result<Self, Error> {
+        // Self-hosted servers often run without authentication.
+        let api_key = match options.base_url {
+            _ if options.dry_run => None,
+            Some(_) => options.key_sources.lookup("OPENAI_API_KEY", "openai")?,
+            None => Some(options.key_sources.require("OPENAI_API_KEY", "openai")?),
+        };
+        let base_url = options
+            .base_url
+            .unwrap_or_else(|| DEFAULT_BASE_URL.to_string());
+        let model = options.model.unwrap_or_else(|| DEFAULT_MODEL.to_string());
+        Ok(Self {
+            client: Client::new(),
+            auth: api_key.map_or(Auth::None, Auth::Bearer),
+            // Compatible servers may not support `response_format`, and
+            // OpenAI rejects it for older models.
+            structured_output: options
+                .structured_output
+                .unwrap_or(base_url == DEFAULT_BASE_URL && supports_json_schema(&model)),
+            model,
+            url: format!("{}/chat/completions", base_url.trim_end_matches('/')),
+            sampling: options.sampling,
+        })
+    }
+
+    /// Targets an Azure OpenAI deployment, using the model as the deployment
+    /// name.
+    pub fn azure(options: ProviderOptions) -> Result<Self, Error> {
+        let auth = if options.dry_run {
+            Auth::None
+        } else {
+            Auth::ApiKey(
+                options
+                    .key_sources
+                    .require("AZURE_OPENAI_API_KEY", "azure")?,
+            )
+        };
+        let endpoint = options
+            .base_url
+            .or_else(|| std::env::var("AZURE_OPENAI_ENDPOINT").ok())
+            .ok_or_else(|| {
+                Error::Provider(
+                    "no Azure OpenAI endpoint given, set AZURE_OPENAI_ENDPOINT or pass --base-url"
+                        .to_string(),
+                )
+            })?;
+        let deployment = options.model.ok_or_else(|| {
+            Error::Provider("no Azure OpenAI deployment given, pass one with --model".to_string())
+        })?;
+        let api_version = options
+            .api_version
+            .or_else(|| std::env::var("AZURE_OPENAI_API_VERSION").ok())
+            .unwrap_or_else(|| DEFAULT_AZURE_API_VERSION.to_string());
+        Ok(Self {
+            client: Client::new(),
+            auth,
+            url: format!(
+                "{}/openai/deployments/{}/chat/completions?api-version={}",
+                endpoint.trim_end_matches('/'),
+                deployment,
+                api_version
+            ),
+            model: deployment,
+            sampling: options.sampling,
+            // `json_schema` needs a newer API version than the default.
+            structured_output: options.structured_output.unwrap_or(false),
+        })
+    }
+
+    /// The request body, with `seed_offset` added to the request's own to
+    /// vary the seed between otherwise identical requests.
+    fn body(&self, request: &ChatRequest<'_>, n: usize, seed_offset: u64) -> serde_json::Value {
+        let mut body = serde_json::json!({
+            "model": self.model,
+            "messages": [
+                {"role": "system", "content": request.system},
+                {"role": "user", "content": request.content}
+            ],
+            "n": n
+        });
+        if let Some(temperature) = self.sampling.temperature {
+            body["temperature"] = temperature.into();
+        }
+        if let Some(top_p) = self.sampling.top_p {
+            body["top_p"] = top_p.into();
+        }
+        if let Some(max_tokens) = self.sampling.max_tokens {
+            body["max_tokens"] = max_tokens.into();
+        }
+        if let Some(seed) = self.sampling.seed {
+            body["seed"] = seed
+                .wrapping_add(request.seed_offset)
+                .wrapping_add(seed_offset)
+                .into();
+        }
+        if let (Some(schema), true) = (request.schema, self.structured_output) {
+            body["response_format"] = serde_json::json!({
+                "type": "json_schema",
+                "json_schema": {"name": "commit_message", "strict": true, "schema": schema}
+            });
+        }
+        body
+    }
+
+    async fn request(
+        &self,
+        request: &ChatRequest<'_>,
+        n: usize,
+        seed_offset: u64,
+    ) -> Result<Response, Error> {
+        let builder = self
+            .client
+            .post(&self.url)
+            .header("Content-Type", "application/json");
+        let builder = match &self.auth {
+            Auth::None => builder,
+            Auth::Bearer(api_key) => builder.header("Authorization", format!("Bearer {}", api_key)),
+            Auth::ApiKey(api_key) => builder.header("api-key", api_key),
+        };
+
+        let response = builder
+            .json(&self.body(request, n, seed_offset))
+            .send()
+            .await?;
+        let response: OpenAIResponse = read_json(response).await?;
+
+        Ok(Response {
+            completions: response
+                .choices
+                .into_iter()
+                .map(|choice| Completion {
+                    text: choice.message.content,
+                    finish_reason: choice.finish_reason,
+                })
+                .collect(),
+            usage: response.usage.map_or_else(Usage::default, |usage| Usage {
+                prompt_tokens: usage.prompt_tokens,
+                completion_tokens: usage.completion_tokens,
+            }),
+        })
+    }
+}
+
+#[async_trait]
+impl Provider for OpenAI {
+    async fn complete(&self, request: &ChatRequest<'_>) -> Result<Response, Error> {
+        let response = self.request(request, request.n, 0).await?;
+
+        // Some OpenAI-compatible servers (vLLM, llama.cpp) ignore `n` and
+        // only return a single choice, so make up the difference with
+        // parallel single-choice requests, each with its own seed.
+        if response.completions.len() < request.n {
+            let missing = request.n - response.completions.len();
+            let extra =
+                try_join_all((1..=missing as u64).map(|i| self.request(request, 1, i))).await?;
+            return Ok(std::iter::once(response).chain(extra).collect());
+        }
+
+        Ok(response)
+    }
+
+    async fn payload(&self, request: &ChatRequest<'_>) -> Result<Payload, Error> {
+        Ok(Payload {
+            url: self.url.clone(),
+            body: self.body(request, request.n, 0),
+            times: 1,
+        })
+    }
+
+    fn context_window(&self) -> usize {
+        context_window(&self.model)
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+}
+
+/// Whether OpenAI accepts a `json_schema` response format for `model`.
+fn supports_json_schema(model: &str) -> bool {
+    const PREFIXES: &[&str] = &[
+        "gpt-4o-mini",
+        "gpt-4o-2024-08-06",
+        "gpt-4o-2024-11-20",
+        "gpt-4.1",
+        "gpt-5",
+        "o3",
+        "o4-mini",
+    ];
+    // The `gpt-4o` alias points at a snapshot that supports it, unlike the
+    // original `gpt-4o-2024-05-13`.
+    model == "gpt-4o" || PREFIXES.iter().any(|prefix| model.starts_with(prefix))
+}
+
+/// Known context windows, falling back to a conservative default for
+/// unknown and self-hosted models.
+fn context_window(model: &str) -> usize {
+    const WINDOWS: &[(&str, usize)] = &[
+        ("gpt-4o", 128_000),
+        ("gpt-4-turbo", 128_000),
+        ("gpt-4-32k", 32_768),
+        ("gpt-4", 8_192),
+        ("gpt-3.5-turbo-instruct", 4_096),
+        ("gpt-3.5-turbo", 16_385),
+        ("gpt-35-turbo", 16_385),
+    ];
+    WINDOWS
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map_or(8_192, |&(_, window)| window)
+}
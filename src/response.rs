@@ -0,0 +1,303 @@
+//! Extracting and validating commit messages from the model's completions.
+
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashSet;
+
+use crate::conventional::Conventional;
+use crate::message;
+use crate::provider::Completion;
+use crate::ticket::Tickets;
+
+/// A validated commit message.
+pub struct Suggestion {
+    pub message: String,
+    /// Why the model stopped generating the completion it came from.
+    pub finish_reason: Option<String>,
+}
+
+/// Collects the valid, distinct messages from a series of completions.
+pub struct Candidates<'a> {
+    conventional: Option<&'a Conventional>,
+    body: bool,
+    max_subject_length: usize,
+    tickets: &'a Tickets,
+    suggestions: Vec<Suggestion>,
+    /// Normalised subjects of the messages so far, to spot near duplicates.
+    seen: HashSet<String>,
+}
+
+impl<'a> Candidates<'a> {
+    pub fn new(
+        conventional: Option<&'a Conventional>,
+        body: bool,
+        max_subject_length: usize,
+        tickets: &'a Tickets,
+    ) -> Self {
+        Self {
+            conventional,
+            body,
+            max_subject_length,
+            tickets,
+            suggestions: Vec::new(),
+            seen: HashSet::new(),
+        }
+    }
+
+    /// The schema to request completions in.
+    pub fn schema(&self) -> serde_json::Value {
+        schema(self.conventional.is_some(), self.body)
+    }
+
+    pub fn len(&self) -> usize {
+        self.suggestions.len()
+    }
+
+    /// Normalises `completion` into a message and keeps it, unless it's
+    /// invalid or near identical to one already kept.
+    pub fn add(&mut self, completion: &Completion) {
+        let message = parse(&completion.text);
+        let (subject, body) = message::split(&message);
+
+        let subject = subject.split_whitespace().collect::<Vec<_>>().join(" ");
+        // Subjects are titles, but an ellipsis is left alone.
+        let subject = match subject.strip_suffix('.') {
+            Some(stripped) if !stripped.ends_with('.') => stripped.to_string(),
+            _ => subject,
+        };
+        let subject = match self.conventional {
+            Some(conventional) => match conventional.repair(&subject) {
+                Some(subject) => subject,
+                None => return,
+            },
+            None => subject,
+        };
+        if subject.is_empty() || subject.chars().count() > self.max_subject_length {
+            return;
+        }
+
+        let key = subject
+            .to_lowercase()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        if !self.seen.insert(key) {
+            return;
+        }
+
+        let message = if self.body {
+            message::join(&subject, &message::wrap(body, message::BODY_WIDTH))
+        } else {
+            subject
+        };
+        self.suggestions.push(Suggestion {
+            message: self.tickets.apply(&message, self.conventional.is_some()),
+            finish_reason: completion.finish_reason.clone(),
+        });
+    }
+
+    pub fn into_suggestions(self) -> Vec<Suggestion> {
+        self.suggestions
+    }
+}
+
+/// A commit message as returned with structured output.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Structured {
+    subject: String,
+    body: String,
+    #[serde(rename = "type")]
+    ty: String,
+    scope: String,
+}
+
+/// The JSON schema completions are requested in, where the provider supports
+/// it. `type` and `scope` are only asked for in Conventional Commits mode,
+/// and `body` only when a body is wanted.
+pub fn schema(conventional: bool, body: bool) -> serde_json::Value {
+    let mut properties = serde_json::Map::new();
+    if conventional {
+        properties.insert(
+            "type".to_string(),
+            serde_json::json!({"type": "string", "description": "The Conventional Commits type"}),
+        );
+        properties.insert(
+            "scope".to_string(),
+            serde_json::json!({"type": "string", "description": "The Conventional Commits scope, or an empty string for none"}),
+        );
+    }
+    properties.insert(
+        "subject".to_string(),
+        serde_json::json!({"type": "string", "description": "The subject line, without any type or scope prefix"}),
+    );
+    if body {
+        properties.insert(
+            "body".to_string(),
+            serde_json::json!({"type": "string", "description": "The body explaining what changed and why"}),
+        );
+    }
+    let required: Vec<&String> = properties.keys().collect();
+    serde_json::json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false
+    })
+}
+
+/// Turns a completion into a commit message, reading it as structured output
+/// when it parses as such and cleaning it up as free text otherwise.
+fn parse(completion: &str) -> String {
+    match serde_json::from_str::<Structured>(completion.trim()) {
+        Ok(structured) if !structured.subject.trim().is_empty() => {
+            let subject = structured.subject.trim();
+            let subject = match (structured.ty.trim(), structured.scope.trim()) {
+                ("", _) => subject.to_string(),
+                (ty, "") => format!("{}: {}", ty, subject),
+                (ty, scope) => format!("{}({}): {}", ty, scope, subject),
+            };
+            message::join(&subject, structured.body.trim())
+        }
+        _ => clean(completion),
+    }
+}
+
+/// Strips the decoration models tend to add around free text messages, e.g.
+/// code fences, `Commit message:` labels, list markers and quotes.
+fn clean(completion: &str) -> String {
+    let label = Regex::new(r"(?i)^(?:suggested\s+)?commit(?:\s+message)?\s*:\s*").unwrap();
+    let list_marker = Regex::new(r"^(?:[-*•]|\d+[.)])\s+").unwrap();
+
+    let text = completion
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("```"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    // The label may be on a line of its own above the message.
+    let text = label.replace(text.trim(), "");
+    let (subject, body) = message::split(strip_quotes(text.trim()));
+
+    let subject = list_marker.replace(subject, "");
+    let subject = label.replace(&subject, "");
+    message::join(strip_quotes(subject.trim()), body)
+}
+
+fn strip_quotes(s: &str) -> &str {
+    for quote in ['"', '\'', '`'] {
+        if s.len() >= 2 && s.starts_with(quote) && s.ends_with(quote) {
+            return s[1..s.len() - 1].trim();
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ticket::Placement;
+
+    fn completion(text: &str) -> Completion {
+        Completion {
+            text: text.to_string(),
+            finish_reason: Some("stop".to_string()),
+        }
+    }
+
+    fn messages(
+        conventional: Option<&Conventional>,
+        body: bool,
+        completions: &[&str],
+    ) -> Vec<String> {
+        let tickets = Tickets::new(Vec::new(), Placement::None, None);
+        let mut candidates = Candidates::new(conventional, body, 50, &tickets);
+        for text in completions {
+            candidates.add(&completion(text));
+        }
+        candidates
+            .into_suggestions()
+            .into_iter()
+            .map(|suggestion| suggestion.message)
+            .collect()
+    }
+
+    #[test]
+    fn clean_strips_fences_and_labels() {
+        assert_eq!(clean("```\nAdd parser\n```"), "Add parser");
+        assert_eq!(clean("Commit message:\nAdd parser"), "Add parser");
+        assert_eq!(clean("Suggested commit: `Add parser`"), "Add parser");
+    }
+
+    #[test]
+    fn clean_strips_list_markers_and_quotes() {
+        assert_eq!(clean("1. \"Add parser\""), "Add parser");
+        assert_eq!(
+            clean("- 'Add parser'\n\nWith a body"),
+            "Add parser\n\nWith a body"
+        );
+    }
+
+    #[test]
+    fn parse_reads_structured_output() {
+        assert_eq!(
+            parse(r#"{"type": "feat", "scope": "cli", "subject": "Add flag", "body": "Why."}"#),
+            "feat(cli): Add flag\n\nWhy."
+        );
+        assert_eq!(
+            parse(r#"{"type": "fix", "scope": "", "subject": "Fix it"}"#),
+            "fix: Fix it"
+        );
+        // JSON without a subject is treated as free text.
+        assert_eq!(parse(r#"{"message": "x"}"#), r#"{"message": "x"}"#);
+    }
+
+    #[test]
+    fn add_normalises_subjects() {
+        assert_eq!(
+            messages(
+                None,
+                false,
+                &["Add   the parser.", "Wait for it...", "Fix it\n\nBody"]
+            ),
+            ["Add the parser", "Wait for it...", "Fix it"]
+        );
+    }
+
+    #[test]
+    fn add_skips_near_duplicates() {
+        assert_eq!(
+            messages(
+                None,
+                false,
+                &["Add parser", "add parser.", "`Add  parser`", "Add lexer"]
+            ),
+            ["Add parser", "Add lexer"]
+        );
+    }
+
+    #[test]
+    fn add_skips_invalid_messages() {
+        let long = "x".repeat(51);
+        assert_eq!(messages(None, false, &["", &long, "Ok"]), ["Ok"]);
+
+        let conventional = Conventional::new(vec!["feat".to_string()], Vec::new());
+        assert_eq!(
+            messages(
+                Some(&conventional),
+                false,
+                &["Add parser", "feature: add lexer"]
+            ),
+            ["feat: add lexer"]
+        );
+    }
+
+    #[test]
+    fn add_wraps_bodies() {
+        let completion = "Add parser\n\nParse array literals in the expression grammar so that configuration files can declare lists of values without the\nstring splitting workaround.";
+        assert_eq!(
+            messages(None, true, &[completion]),
+            ["Add parser\n\nParse array literals in the expression grammar so that configuration\nfiles can declare lists of values without the string splitting\nworkaround."]
+        );
+    }
+}
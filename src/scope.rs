@@ -0,0 +1,61 @@
+//! Conventional Commits scope inference from the staged paths.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Files marking the root of a crate or package.
+const MANIFESTS: &[&str] = &[
+    "Cargo.toml",
+    "package.json",
+    "go.mod",
+    "pyproject.toml",
+    "setup.py",
+    "pom.xml",
+    "build.gradle",
+    "build.gradle.kts",
+    "Package.swift",
+];
+
+/// Infers the scopes touched by `paths`, in order of first appearance.
+///
+/// Each path is matched against the longest prefix in `mappings`, falling
+/// back to the name of the closest directory below `root` containing a
+/// package manifest. Paths matching neither don't contribute a scope.
+pub fn infer(paths: &[String], root: &Path, mappings: &BTreeMap<String, String>) -> Vec<String> {
+    let mut scopes: Vec<String> = Vec::new();
+    for path in paths {
+        let scope = mapped_scope(path, mappings).or_else(|| package_scope(path, root));
+        if let Some(scope) = scope {
+            if !scopes.contains(&scope) {
+                scopes.push(scope);
+            }
+        }
+    }
+    scopes
+}
+
+fn mapped_scope(path: &str, mappings: &BTreeMap<String, String>) -> Option<String> {
+    mappings
+        .iter()
+        .filter(|(prefix, _)| {
+            let prefix = prefix.trim_end_matches('/');
+            path == prefix || path.starts_with(&format!("{}/", prefix))
+        })
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, scope)| scope.clone())
+}
+
+fn package_scope(path: &str, root: &Path) -> Option<String> {
+    Path::new(path)
+        .ancestors()
+        .skip(1)
+        // The repository root is the whole project rather than a scope.
+        .take_while(|dir| !dir.as_os_str().is_empty())
+        .find(|dir| {
+            MANIFESTS
+                .iter()
+                .any(|manifest| root.join(dir).join(manifest).is_file())
+        })
+        .and_then(|dir| dir.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+}
@@ -0,0 +1,121 @@
+//! Map-reduce summarisation for diffs too large to send in one request.
+
+use futures::stream::{self, StreamExt, TryStreamExt};
+use indicatif::ProgressBar;
+
+use crate::diff;
+use crate::provider::{ChatRequest, Error, Provider, Response, Usage};
+
+/// The most per-file requests in flight at once.
+const CONCURRENCY: usize = 8;
+
+/// The most files summarised, so that huge changes don't send hundreds of
+/// requests. Only the largest files are summarised beyond this.
+pub const MAX_FILE_REQUESTS: usize = 20;
+
+/// The request summarising a single file.
+pub struct FileRequest {
+    /// The file's `--stat` line.
+    pub stat: String,
+    pub content: String,
+}
+
+/// The requests summarising the largest files, along with the `--stat`
+/// lines of the files left out.
+pub struct FileRequests {
+    pub requests: Vec<FileRequest>,
+    pub unsummarised: Vec<String>,
+}
+
+/// Builds a summary request for each of the `MAX_FILE_REQUESTS` largest
+/// files in `diff`, trimming each file's diff to `budget` tokens.
+pub fn file_requests(diff: &str, budget: usize) -> FileRequests {
+    let files = diff::parse(diff);
+    let mut by_size: Vec<usize> = (0..files.len()).collect();
+    by_size.sort_by_key(|&i| std::cmp::Reverse(files[i].additions + files[i].deletions));
+    by_size.truncate(MAX_FILE_REQUESTS);
+
+    let mut requests = Vec::new();
+    let mut unsummarised = Vec::new();
+    for (i, file) in files.iter().enumerate() {
+        if !by_size.contains(&i) {
+            unsummarised.push(file.stat());
+            continue;
+        }
+        requests.push(FileRequest {
+            stat: file.stat(),
+            content: format!(
+                "Summarise the following changes to {} in one or two sentences. Do not include an explanation.\n\n```\n{}\n```",
+                file.path,
+                diff::trim(&file.text(), budget).text
+            ),
+        });
+    }
+    FileRequests {
+        requests,
+        unsummarised,
+    }
+}
+
+/// Sends each file's request and combines the summaries into text that can
+/// stand in for the diff in the final prompt, along with the tokens used.
+pub async fn summarize(
+    provider: &dyn Provider,
+    system: &str,
+    file_requests: &FileRequests,
+    pb: &ProgressBar,
+) -> Result<(String, Usage), Error> {
+    let requests = &file_requests.requests;
+    let total = requests.len();
+    pb.set_message(format!("Summarising changes (0/{} files)...", total));
+
+    let mut done = 0;
+    let responses: Vec<Response> = stream::iter(requests)
+        .map(|request| summarize_file(provider, system, request))
+        .buffered(CONCURRENCY)
+        .inspect_ok(|_| {
+            done += 1;
+            pb.set_message(format!("Summarising changes ({}/{} files)...", done, total));
+        })
+        .try_collect()
+        .await?;
+
+    pb.set_message("Fetching suggested commit messages...");
+
+    let mut out = String::from(
+        "The diff is too large to include, so here is a summary of the changes to each file:\n",
+    );
+    let mut usage = Usage::default();
+    for (request, mut response) in requests.iter().zip(responses) {
+        let summary = response
+            .completions
+            .pop()
+            .map(|completion| completion.text)
+            .unwrap_or_default();
+        out.push_str(&format!("\n{}\n{}\n", request.stat, summary.trim()));
+        usage += response.usage;
+    }
+    if !file_requests.unsummarised.is_empty() {
+        out.push_str("\nOther files changed:\n");
+        for stat in &file_requests.unsummarised {
+            out.push_str(&format!("{}\n", stat));
+        }
+    }
+    Ok((out, usage))
+}
+
+async fn summarize_file(
+    provider: &dyn Provider,
+    system: &str,
+    request: &FileRequest,
+) -> Result<Response, Error> {
+    provider
+        .complete(&ChatRequest {
+            system,
+            content: &request.content,
+            n: 1,
+            schema: None,
+            seed_offset: 0,
+        })
+        .await
+}
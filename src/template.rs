@@ -0,0 +1,169 @@
+//! Prompt templates with `{{placeholder}}` variables.
+//!
+//! Named templates are looked up as `<name>.txt` in the repository's
+//! `.git-commit-gpt/templates` directory, then in the `templates` directory
+//! next to the global config file.
+
+use regex::{Captures, Regex};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::config;
+
+pub const REPO_TEMPLATE_DIR: &str = ".git-commit-gpt/templates";
+const TEMPLATE_EXTENSION: &str = "txt";
+
+/// The placeholders a template may use.
+pub const VARIABLES: &[&str] = &[
+    "diff",
+    "stat",
+    "branch",
+    "files",
+    "recent_commits",
+    "language",
+    "instructions",
+];
+
+#[derive(Debug)]
+pub enum Error {
+    Read(PathBuf, std::io::Error),
+    NotFound(String, Vec<PathBuf>),
+    Unknown(PathBuf, String),
+    /// The template would send no diff.
+    MissingDiff(PathBuf),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Read(path, e) => write!(f, "failed to read {}: {}", path.display(), e),
+            Error::NotFound(name, searched) => write!(
+                f,
+                "template '{}' not found (searched {})",
+                name,
+                searched
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Error::Unknown(path, name) => write!(
+                f,
+                "unknown placeholder {{{{{}}}}} in {}, expected one of {}",
+                name,
+                path.display(),
+                VARIABLES.join(", ")
+            ),
+            Error::MissingDiff(path) => {
+                write!(f, "{} has no {{{{diff}}}} placeholder", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// The values substituted for each placeholder.
+pub struct Variables<'a> {
+    pub diff: &'a str,
+    /// One `path | +additions -deletions` line per file.
+    pub stat: &'a str,
+    pub branch: &'a str,
+    /// The staged paths, one per line.
+    pub files: &'a str,
+    pub recent_commits: &'a str,
+    pub language: &'a str,
+    /// The built-in formatting instructions, e.g. for Conventional Commits.
+    pub instructions: &'a str,
+}
+
+impl Variables<'_> {
+    fn get(&self, name: &str) -> &str {
+        match name {
+            "diff" => self.diff,
+            "stat" => self.stat,
+            "branch" => self.branch,
+            "files" => self.files,
+            "recent_commits" => self.recent_commits,
+            "language" => self.language,
+            "instructions" => self.instructions,
+            _ => unreachable!("placeholders are checked when loading"),
+        }
+    }
+}
+
+pub struct Template {
+    text: String,
+    placeholder: Regex,
+}
+
+impl Template {
+    /// Loads `name`, either a path to a template file or the name of one in
+    /// the template directories.
+    pub fn load(name: &str) -> Result<Self, Error> {
+        let path = find(name)?;
+        let text = std::fs::read_to_string(&path).map_err(|e| Error::Read(path.clone(), e))?;
+        let placeholder = Regex::new(r"\{\{\s*(\w+)\s*\}\}").unwrap();
+        if let Some(unknown) = placeholder
+            .captures_iter(&text)
+            .map(|caps| caps[1].to_string())
+            .find(|name| !VARIABLES.contains(&name.as_str()))
+        {
+            return Err(Error::Unknown(path, unknown));
+        }
+        if !placeholder
+            .captures_iter(&text)
+            .any(|caps| &caps[1] == "diff")
+        {
+            return Err(Error::MissingDiff(path));
+        }
+        Ok(Self { text, placeholder })
+    }
+
+    pub fn render(&self, variables: &Variables) -> String {
+        self.placeholder
+            .replace_all(&self.text, |caps: &Captures| {
+                variables.get(&caps[1]).to_string()
+            })
+            .trim()
+            .to_string()
+    }
+}
+
+/// Whether `name` is a bare template name rather than a path.
+pub fn is_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['/', '\\']) && !name.contains("..")
+}
+
+/// The path of the repository's template called `name`, if it has one.
+pub fn repo_template(name: &str) -> Option<PathBuf> {
+    if !is_name(name) {
+        return None;
+    }
+    let path = config::repo_root()?
+        .join(REPO_TEMPLATE_DIR)
+        .join(format!("{}.{}", name, TEMPLATE_EXTENSION));
+    path.is_file().then_some(path)
+}
+
+fn find(name: &str) -> Result<PathBuf, Error> {
+    let path = Path::new(name);
+    if path.is_file() {
+        return Ok(path.to_path_buf());
+    }
+
+    let file = format!("{}.{}", name, TEMPLATE_EXTENSION);
+    let dirs = [
+        config::repo_root().map(|root| root.join(REPO_TEMPLATE_DIR)),
+        config::global_config_dir().map(|dir| dir.join("templates")),
+    ];
+    let searched: Vec<PathBuf> = dirs
+        .into_iter()
+        .flatten()
+        .map(|dir| dir.join(&file))
+        .collect();
+    match searched.iter().find(|path| path.is_file()) {
+        Some(path) => Ok(path.clone()),
+        None => Err(Error::NotFound(name.to_string(), searched)),
+    }
+}
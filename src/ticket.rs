@@ -0,0 +1,182 @@
+//! Issue keys taken from the branch name, e.g. `PROJ-1234` from
+//! `feature/PROJ-1234-add-login`.
+
+use crate::config;
+use crate::message;
+use clap::ValueEnum;
+use regex::Regex;
+use serde::Deserialize;
+
+/// Matches Jira style keys. When a pattern has a `ticket` group only that
+/// part of the match is used.
+pub const DEFAULT_PATTERN: &str = r"\b[A-Z][A-Z0-9]+-[0-9]+\b";
+
+/// Where issue keys are inserted into each message.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Placement {
+    /// Leave messages unchanged
+    None,
+    /// Before the subject, after the type and scope in Conventional Commits
+    Prefix,
+    /// After the subject
+    Suffix,
+    /// In a trailer after the body
+    Trailer,
+}
+
+impl Placement {
+    fn default_template(self) -> &'static str {
+        match self {
+            Placement::None => "",
+            Placement::Prefix => "{ticket} ",
+            Placement::Suffix => " ({ticket})",
+            Placement::Trailer => "Refs: {ticket}",
+        }
+    }
+}
+
+pub struct Tickets {
+    ids: Vec<String>,
+    placement: Placement,
+    template: String,
+}
+
+impl Tickets {
+    /// Inserts `ids` at `placement`. `template` is inserted once per message
+    /// with `{ticket}` replaced by the keys, defaulting to one suited to
+    /// `placement`.
+    pub fn new(ids: Vec<String>, placement: Placement, template: Option<&str>) -> Self {
+        Self {
+            ids,
+            placement,
+            template: template
+                .unwrap_or_else(|| placement.default_template())
+                .to_string(),
+        }
+    }
+
+    /// Extracts the issue keys matching `pattern` from the current branch,
+    /// to insert as with `new`.
+    pub fn from_branch(
+        pattern: &str,
+        placement: Placement,
+        template: Option<&str>,
+    ) -> Result<Self, regex::Error> {
+        let regex = Regex::new(pattern)?;
+        let mut ids: Vec<String> = Vec::new();
+        if let Some(branch) = config::current_branch() {
+            for caps in regex.captures_iter(&branch) {
+                let id = caps.name("ticket").unwrap_or_else(|| caps.get(0).unwrap());
+                if !ids.iter().any(|existing| existing == id.as_str()) {
+                    ids.push(id.as_str().to_string());
+                }
+            }
+        }
+        Ok(Self::new(ids, placement, template))
+    }
+
+    /// Inserts the issue keys into `message` unless it already mentions all
+    /// of them. `conventional` keeps a prefix after the `type(scope): `
+    /// header.
+    pub fn apply(&self, message: &str, conventional: bool) -> String {
+        if self.ids.is_empty()
+            || self.placement == Placement::None
+            || self.ids.iter().all(|id| message.contains(id.as_str()))
+        {
+            return message.to_string();
+        }
+        let insert = self.template.replace("{ticket}", &self.ids.join(", "));
+
+        let (subject, body) = message::split(message);
+        match self.placement {
+            Placement::None => message.to_string(),
+            Placement::Prefix => {
+                let subject = match subject.split_once(": ") {
+                    Some((header, rest)) if conventional => {
+                        format!("{}: {}{}", header, insert, rest)
+                    }
+                    _ => format!("{}{}", insert, subject),
+                };
+                message::join(&subject, body)
+            }
+            Placement::Suffix => message::join(&format!("{}{}", subject, insert), body),
+            Placement::Trailer => format!("{}\n\n{}", message.trim(), insert),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tickets(ids: &[&str], placement: Placement) -> Tickets {
+        Tickets::new(
+            ids.iter().map(|id| id.to_string()).collect(),
+            placement,
+            None,
+        )
+    }
+
+    #[test]
+    fn apply_prefixes_after_a_conventional_header() {
+        let tickets = tickets(&["PROJ-1"], Placement::Prefix);
+        assert_eq!(
+            tickets.apply("feat(auth): add login", true),
+            "feat(auth): PROJ-1 add login"
+        );
+        assert_eq!(tickets.apply("Add login", false), "PROJ-1 Add login");
+        assert_eq!(
+            tickets.apply("Note: add login\n\nBody", false),
+            "PROJ-1 Note: add login\n\nBody"
+        );
+    }
+
+    #[test]
+    fn apply_appends_a_suffix_to_the_subject() {
+        let tickets = tickets(&["PROJ-1", "PROJ-2"], Placement::Suffix);
+        assert_eq!(
+            tickets.apply("Add login\n\nBody", false),
+            "Add login (PROJ-1, PROJ-2)\n\nBody"
+        );
+    }
+
+    #[test]
+    fn apply_adds_a_trailer() {
+        let tickets = tickets(&["PROJ-1"], Placement::Trailer);
+        assert_eq!(
+            tickets.apply("Add login", false),
+            "Add login\n\nRefs: PROJ-1"
+        );
+        let tickets = Tickets::new(
+            vec!["PROJ-1".to_string()],
+            Placement::Trailer,
+            Some("Closes {ticket}"),
+        );
+        assert_eq!(
+            tickets.apply("Add login\n\nBody", false),
+            "Add login\n\nBody\n\nCloses PROJ-1"
+        );
+    }
+
+    #[test]
+    fn apply_leaves_messages_that_mention_every_id() {
+        let tickets = tickets(&["PROJ-1", "PROJ-2"], Placement::Prefix);
+        assert_eq!(
+            tickets.apply("Fix PROJ-1 and PROJ-2", false),
+            "Fix PROJ-1 and PROJ-2"
+        );
+        assert_eq!(
+            tickets.apply("Fix PROJ-1", false),
+            "PROJ-1, PROJ-2 Fix PROJ-1"
+        );
+        assert_eq!(
+            Tickets::new(Vec::new(), Placement::Prefix, None).apply("Fix it", false),
+            "Fix it"
+        );
+        assert_eq!(
+            Tickets::new(vec!["PROJ-1".to_string()], Placement::None, None).apply("Fix it", false),
+            "Fix it"
+        );
+    }
+}